@@ -0,0 +1,27 @@
+//! Compresses the default word list and n-gram table so they can be embedded in the binary
+
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+const DATA_FILES: &[&str] = &["words_alpha.txt", "quadgrams.txt"];
+
+fn main() -> io::Result<()> {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    for name in DATA_FILES {
+        println!("cargo:rerun-if-changed={}", name);
+
+        let mut input = BufReader::new(File::open(name)?);
+        let output = File::create(Path::new(&out_dir).join(format!("{}.gz", name)))?;
+        let mut encoder = GzEncoder::new(output, Compression::best());
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?;
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    Ok(())
+}
@@ -0,0 +1,152 @@
+#![allow(dead_code)]
+
+use std::fmt;
+
+use crate::{alpha, fitness::Fitness};
+
+/// Multipliers coprime with 26, i.e. the ones that have an inverse
+const VALID_A: [u8; 12] = [1, 3, 5, 7, 9, 11, 15, 17, 19, 21, 23, 25];
+
+/// Affine cipher: plain letter x is encrypted as (a * x + b) mod 26
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Affine {
+    a: u8,
+    b: u8,
+    /// Inverse of `a` mod 26, for decryption
+    a_inv: u8,
+}
+
+impl fmt::Debug for Affine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Affine---")?;
+        writeln!(f, "a: {}, b: {}", self.a, self.b)?;
+        Ok(())
+    }
+}
+
+impl Affine {
+    /// `a` must be coprime with 26; both are taken mod 26
+    pub fn new(a: i32, b: i32) -> Result<Self, &'static str> {
+        let a = a.rem_euclid(26) as u8;
+        let b = b.rem_euclid(26) as u8;
+        let a_inv = (1..26)
+            .find(|inv| (a as u32 * inv) % 26 == 1)
+            .ok_or("Error creating a key for Affine: a must be coprime with 26")?;
+        Ok(Self {
+            a,
+            b,
+            a_inv: a_inv as u8,
+        })
+    }
+
+    /// Caesar cipher shifting forward by `shift`
+    pub fn caesar(shift: i32) -> Self {
+        Self::new(1, shift).unwrap()
+    }
+
+    /// Atbash: the alphabet reversed, a <-> z, b <-> y, ...
+    pub fn atbash() -> Self {
+        Self::new(25, 25).unwrap()
+    }
+
+    pub fn a(&self) -> u8 {
+        self.a
+    }
+
+    pub fn b(&self) -> u8 {
+        self.b
+    }
+
+    /// Encrypt text, keeping case; chars other than ascii letters are left unchanged
+    pub fn encrypt(&self, text: &str) -> String {
+        text.chars()
+            .map(|c| {
+                self.map_char(c, |x| {
+                    ((self.a as u32 * x as u32 + self.b as u32) % 26) as u8
+                })
+            })
+            .collect()
+    }
+
+    /// Decrypt text, keeping case; chars other than ascii letters are left unchanged
+    pub fn decrypt(&self, text: &str) -> String {
+        text.chars().map(|c| self.decrypt_char(c)).collect()
+    }
+
+    fn decrypt_char(&self, c: char) -> char {
+        self.map_char(c, |y| {
+            (self.a_inv as u32 * (y as u32 + 26 - self.b as u32) % 26) as u8
+        })
+    }
+
+    fn map_char(&self, c: char, f: impl Fn(u8) -> u8) -> char {
+        match alpha::get_letter_pos(c.to_ascii_lowercase()) {
+            Some(pos) => alpha::match_case((b'a' + f(pos)) as char, c),
+            None => c,
+        }
+    }
+
+    /// Score the decryption of text under every key and return the `top` best keys, best first
+    pub fn crack(text: &str, fitness: &Fitness, top: usize) -> Vec<(Self, f64)> {
+        let lower = text.to_ascii_lowercase();
+
+        let mut ranked: Vec<(Self, f64)> = VALID_A
+            .iter()
+            .flat_map(|a| (0..26).map(move |b| Self::new(*a as i32, b).unwrap()))
+            .map(|cipher| (cipher, fitness.score_text(&cipher.decrypt(&lower))))
+            .collect();
+
+        // stable, so ties keep the simpler keys (smaller a, then smaller b) first
+        ranked.sort_by(|x, y| y.1.partial_cmp(&x.1).unwrap());
+        ranked.truncate(top);
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caesar::Caesar;
+    use crate::ngram::NGram;
+    use crate::test_text::{ATBASH, PLAIN};
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let cipher = Affine::new(5, 8).unwrap();
+        assert_eq!("Ihhwvc Swfrcp!", cipher.encrypt("Affine Cipher!"));
+        assert_eq!("Affine Cipher!", cipher.decrypt("Ihhwvc Swfrcp!"));
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(Affine::new(13, 1).is_err());
+        assert!(Affine::new(2, 1).is_err());
+        assert_eq!(Affine::new(-1, 27).unwrap(), Affine::new(25, 1).unwrap());
+    }
+
+    #[test]
+    fn test_special_cases() {
+        let text = "Hello, World!";
+        assert_eq!(Caesar::encrypt(text, 3), Affine::caesar(3).encrypt(text));
+        assert_eq!("Svool, Dliow!", Affine::atbash().encrypt(text));
+        assert_eq!(
+            text,
+            Affine::atbash().encrypt(&Affine::atbash().encrypt(text))
+        );
+        assert_eq!(Affine::atbash().encrypt(PLAIN), ATBASH);
+    }
+
+    #[test]
+    fn test_crack() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let cipher = Affine::new(7, 3).unwrap();
+        let text =
+            cipher.encrypt("the quick brown fox jumps over the lazy dog while the cat sleeps");
+
+        let ranked = Affine::crack(&text, &fitness, 5);
+        assert_eq!(5, ranked.len());
+        assert_eq!(cipher, ranked[0].0);
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+}
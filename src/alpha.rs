@@ -16,20 +16,28 @@ pub fn get_letter_pos(letter: char) -> Option<u8> {
     }
 }
 
+/// Give `letter` the same case as `like`
+pub fn match_case(letter: char, like: char) -> char {
+    if like.is_ascii_uppercase() {
+        letter.to_ascii_uppercase()
+    } else {
+        letter.to_ascii_lowercase()
+    }
+}
+
 pub fn rm_punct(word: &str) -> String {
-    word.replace(
-        |x| match x {
-            ',' | '.' | '!' | ':' | ';' => true,
-            _ => false,
-        },
-        "",
-    )
+    word.replace([',', '.', '!', ':', ';'], "")
 }
 
-/// Common list of two-letter words
-///
-/// From: [https://www3.nd.edu/~busiforc/handouts/cryptography/cryptography%20hints.html](https://www3.nd.edu/~busiforc/handouts/cryptography/cryptography%20hints.html)
-pub const CRIBS_L2: [&str; 24] = [
-    "of", "to", "in", "it", "is", "be", "as", "at", "so", "we", "he", "by", "or", "on", "do", "if",
-    "me", "my", "up", "an", "go", "no", "us", "am",
+/// Relative frequency of each letter in English text, in alphabetical order
+pub const ENGLISH_FREQ: [f64; 26] = [
+    0.08167, 0.01492, 0.02782, 0.04253, 0.12702, 0.02228, 0.02015, 0.06094, 0.06966, 0.00153,
+    0.00772, 0.04025, 0.02406, 0.06749, 0.07507, 0.01929, 0.00095, 0.05987, 0.06327, 0.09056,
+    0.02758, 0.00978, 0.02360, 0.00150, 0.01974, 0.00074,
 ];
+
+/// Index of coincidence of typical English text
+pub const ENGLISH_IOC: f64 = 0.0667;
+
+/// Index of coincidence of uniformly random letters, 1/26
+pub const RANDOM_IOC: f64 = 0.0385;
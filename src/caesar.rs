@@ -33,6 +33,36 @@ pub fn get_percentage(text: &str) -> HashMap<char, f64> {
 
     percent
 }
+/// Chi-squared distance between letter percentages and English; lower means closer to English
+pub fn chi_squared(percent: &HashMap<char, f64>) -> f64 {
+    alpha::ASCII_LOWER
+        .iter()
+        .zip(alpha::ENGLISH_FREQ.iter())
+        .map(|(c, expected)| {
+            let observed = percent.get(c).copied().unwrap_or(0.);
+            (observed - expected).powi(2) / expected
+        })
+        .sum()
+}
+
+/// Probability that two letters picked at random from text are the same. Assumes all chars are in lowercase.
+pub fn index_of_coincidence(text: &str) -> f64 {
+    let mut freq = [0u32; 26];
+    for c in text.chars() {
+        if let Some(pos) = alpha::get_letter_pos(c) {
+            freq[pos as usize] += 1;
+        }
+    }
+
+    let total: u32 = freq.iter().sum();
+    if total < 2 {
+        return 0.;
+    }
+
+    let pairs: u32 = freq.iter().map(|f| f * f.saturating_sub(1)).sum();
+    pairs as f64 / (total * (total - 1)) as f64
+}
+
 pub fn order_percentage(percent: &HashMap<char, f64>) -> Vec<(char, f64)> {
     let mut entries: Vec<(char, f64)> = percent
         .iter()
@@ -45,11 +75,11 @@ pub fn order_percentage(percent: &HashMap<char, f64>) -> Vec<(char, f64)> {
 }
 
 impl Caesar {
-    /// Shift a letter by a certain amount; assumes that letter is a valid lower ascii alphabet, otherwise leave it unchanged
+    /// Shift a letter by a certain amount, keeping its case; chars other than ascii letters are left unchanged
     ///
     /// Negative shift will move backwards
     fn shift(letter: char, shift: i32) -> char {
-        match alpha::get_letter_pos(letter) {
+        match alpha::get_letter_pos(letter.to_ascii_lowercase()) {
             Some(orig_pos) => {
                 let new_pos = match (shift + orig_pos as i32) % ASCII_LEN {
                     x if x < 0 => x + ASCII_LEN,
@@ -57,11 +87,11 @@ impl Caesar {
                 };
 
                 assert!(
-                    new_pos >= 0 && new_pos <= ASCII_LEN,
+                    (0..=ASCII_LEN).contains(&new_pos),
                     "internal error: position should be in range 0 to 27"
                 );
 
-                (b'a' + new_pos as u8) as char
+                alpha::match_case((b'a' + new_pos as u8) as char, letter)
             }
             None => letter,
         }
@@ -72,7 +102,17 @@ impl Caesar {
         a as i32 - b as i32
     }
 
-    /// Shift an entire string with Caesar cipher. Assumes all chars are ascii lower.
+    /// Encrypt text by shifting every letter forward by `shift`; case and other chars are preserved
+    pub fn encrypt(text: &str, shift: i32) -> String {
+        Self::transform(text, shift)
+    }
+
+    /// Decrypt text that was encrypted with `shift`; case and other chars are preserved
+    pub fn decrypt(text: &str, shift: i32) -> String {
+        Self::transform(text, -shift)
+    }
+
+    /// Shift an entire string with Caesar cipher.
     fn transform(text: &str, shift: i32) -> String {
         if shift == 0 {
             return text.to_owned();
@@ -87,7 +127,8 @@ impl Caesar {
         s
     }
 
-    pub fn crack(text: &str, dict: &Dict) -> Option<String> {
+    /// Returns the shift the text was encrypted with, and the decrypted text
+    pub fn crack(text: &str, dict: &Dict) -> Option<(i32, String)> {
         // for now, primitive frequency analysis
         let lower = text.to_ascii_lowercase();
         let percent = get_percentage(&lower);
@@ -96,9 +137,11 @@ impl Caesar {
         // try mapping all to 'e' until one is readable in english
         for trial in order {
             let dist = Self::distance('e', trial.0);
-            let text = Self::transform(&lower, dist);
-            if dict.str_contains_every_word(&text) {
-                return Some(text);
+            if dict.str_contains_every_word(&Self::transform(&lower, dist)) {
+                return Some((
+                    (ASCII_LEN - dist).rem_euclid(ASCII_LEN),
+                    Self::transform(text, dist),
+                ));
             }
         }
         None
@@ -132,6 +175,13 @@ mod tests {
         assert_eq!('u', Caesar::shift('e', -10))
     }
 
+    #[test]
+    fn keeps_case() {
+        assert_eq!('B', Caesar::shift('A', 1));
+        assert_eq!("Khoor, Zruog!", Caesar::encrypt("Hello, World!", 3));
+        assert_eq!("Hello, World!", Caesar::decrypt("Khoor, Zruog!", 3));
+    }
+
     #[test]
     fn multiple_wraps() {
         assert_eq!('a', Caesar::shift('b', 26 + 26 * 2 + 25));
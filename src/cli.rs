@@ -0,0 +1,434 @@
+use std::{
+    fs,
+    io::{self, prelude::*},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use structopt::StructOpt;
+
+use crate::{
+    data,
+    fitness::Scoring,
+    report::Format,
+    simp_sub::{AnnealSchedule, Crib, StopRule},
+};
+
+#[derive(Debug, StructOpt)]
+#[structopt(about = "Analyze and crack classical ciphers")]
+pub enum Command {
+    /// Print letter frequencies, most frequent first
+    Analyze {
+        #[structopt(flatten)]
+        input: Input,
+        #[structopt(flatten)]
+        output: Output,
+    },
+    /// Identify the likely cipher family, then crack the text with it
+    Decode {
+        #[structopt(flatten)]
+        input: Input,
+        #[structopt(flatten)]
+        search: Search,
+        #[structopt(flatten)]
+        data: Data,
+        /// Known plaintext, like "attack at dawn", or "attack at dawn@40" if it starts at letter 40 of the
+        /// ciphertext (counting from 0); may be given more than once. Cracks as a substitution cipher, or as a
+        /// hill cipher with --cipher hill.
+        #[structopt(long = "crib", number_of_values = 1)]
+        cribs: Vec<Crib>,
+        /// Also search the word list for keywords that could have made a substitution key, and print the best N
+        #[structopt(long, value_name = "N")]
+        keywords: Option<usize>,
+        /// Skip identification and crack as this cipher: caesar, affine, vigenere, transposition, playfair,
+        /// hill, homophonic or substitution. Text of symbols without letters, like "12 7 105 ...", is cracked
+        /// as homophonic if it has at least 100 symbols.
+        #[structopt(short, long)]
+        cipher: Option<Cipher>,
+        #[structopt(flatten)]
+        output: Output,
+    },
+    /// Print statistics of the text and the ranking of likely cipher families
+    Identify {
+        #[structopt(flatten)]
+        input: Input,
+    },
+    /// Crack as a substitution cipher, then refine the key by hand
+    Interactive {
+        /// Ciphertext file; stdin is left for the commands
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(flatten)]
+        search: Search,
+        #[structopt(flatten)]
+        data: Data,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Input {
+    /// Ciphertext file; reads stdin if omitted or `-`
+    #[structopt(parse(from_os_str))]
+    file: Option<PathBuf>,
+}
+
+impl Input {
+    pub fn read(&self) -> io::Result<String> {
+        match &self.file {
+            Some(path) if path.as_os_str() != "-" => {
+                fs::read_to_string(path).map_err(|e| data::with_path(e, path))
+            }
+            _ => {
+                let mut buf = String::new();
+                io::stdin().read_to_string(&mut buf)?;
+                Ok(buf)
+            }
+        }
+    }
+}
+
+// How the substitution solver searches for a key; doc comments on flattened structs would replace the
+// subcommand's help text
+#[derive(Debug, StructOpt)]
+pub struct Search {
+    /// Number of hills to climb, or `anneal`; same as --hills and --anneal
+    #[structopt(name = "HILLS|anneal")]
+    legacy: Option<Legacy>,
+    /// Number of random restarts of the hill climber [default: 500, or 20000 with --confirm or --budget]
+    #[structopt(short = "n", long)]
+    hills: Option<usize>,
+    /// Stop climbing once this many hills have reached the best key
+    #[structopt(long, conflicts_with = "anneal")]
+    confirm: Option<usize>,
+    /// Stop climbing after this many seconds
+    #[structopt(long, conflicts_with = "anneal")]
+    budget: Option<f64>,
+    /// Use simulated annealing instead of hill climbing
+    #[structopt(long, conflicts_with = "hills")]
+    anneal: bool,
+    /// Starting temperature of annealing, in log10 score per letter [default: 0.2]; implies --anneal
+    #[structopt(long, conflicts_with_all = &["hills", "confirm", "budget"])]
+    temp: Option<f64>,
+    /// Annealing temperature is multiplied by this after every key tried [default: 0.9997]; implies --anneal
+    #[structopt(long, conflicts_with_all = &["hills", "confirm", "budget"])]
+    cooling: Option<f64>,
+    /// Keys tried by each annealing restart [default: 20000]; implies --anneal
+    #[structopt(long, conflicts_with_all = &["hills", "confirm", "budget"])]
+    iterations: Option<usize>,
+    /// Number of annealing restarts [default: 16]; implies --anneal
+    #[structopt(long, conflicts_with_all = &["hills", "confirm", "budget"])]
+    restarts: Option<usize>,
+    /// Seed for the random number generator, to make runs repeatable; a random one is used and printed otherwise
+    #[structopt(long)]
+    pub seed: Option<u64>,
+    /// Number of best keys with different plaintexts to report
+    #[structopt(long, default_value = "1")]
+    pub top: usize,
+    /// Number of worker threads; defaults to the number of CPUs
+    #[structopt(short = "j", long)]
+    pub threads: Option<usize>,
+}
+
+/// Hills climbed when neither a count nor a stopping rule is given
+const DEFAULT_HILLS: usize = 500;
+
+/// Most hills climbed when stopping adaptively, unless a count is given
+const DEFAULT_ADAPTIVE_HILLS: usize = 20000;
+
+/// Hills climbed from every placement of the cribs, unless a count is given
+const DEFAULT_CRIB_HILLS: usize = 20;
+
+impl Search {
+    /// Whether any option choosing how the substitution solver searches was given, in which case the word
+    /// pattern attack is skipped
+    pub fn is_explicit(&self) -> bool {
+        self.legacy.is_some()
+            || self.hills.is_some()
+            || self.confirm.is_some()
+            || self.budget.is_some()
+            || self.anneal
+            || self.tunes_schedule()
+    }
+
+    /// Fails if the options ask for both hill climbing and annealing in a way structopt can't catch, or the
+    /// budget is not a number of seconds above 0
+    pub fn check(&self) -> Result<(), &'static str> {
+        if let Some(budget) = self.budget {
+            // also rules out NaN, infinity and more seconds than a Duration holds
+            if budget <= 0. || Duration::try_from_secs_f64(budget).is_err() {
+                return Err("--budget must be a number of seconds above 0");
+            }
+        }
+        match self.legacy {
+            Some(Legacy::Hills(_)) if self.anneal || self.tunes_schedule() => {
+                Err("a number of hills can't be given with --anneal or an annealing schedule")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// When hill climbing stops; None for annealing
+    pub fn stop_rule(&self) -> Option<StopRule> {
+        let hills = match self.legacy {
+            Some(Legacy::Hills(n)) => Some(n),
+            Some(Legacy::Anneal) => return None,
+            None if self.anneal || self.tunes_schedule() => return None,
+            None => self.hills,
+        };
+        let adaptive = self.confirm.is_some() || self.budget.is_some();
+        Some(StopRule {
+            max_hills: hills.unwrap_or(if adaptive {
+                DEFAULT_ADAPTIVE_HILLS
+            } else {
+                DEFAULT_HILLS
+            }),
+            confirmations: self.confirm,
+            budget: self.budget.map(Duration::from_secs_f64),
+        })
+    }
+
+    /// Number of hills to climb from every placement of --crib; fails for the options that don't apply to it
+    pub fn crib_hills(&self) -> Result<usize, &'static str> {
+        match self.legacy {
+            Some(Legacy::Hills(n)) => Ok(n),
+            Some(Legacy::Anneal) => Err("--crib can't be used with annealing"),
+            None if self.anneal || self.tunes_schedule() => {
+                Err("--crib can't be used with annealing")
+            }
+            None if self.confirm.is_some() || self.budget.is_some() => {
+                Err("--crib only takes a number of hills, not --confirm or --budget")
+            }
+            None => Ok(self.hills.unwrap_or(DEFAULT_CRIB_HILLS)),
+        }
+    }
+
+    /// Annealing schedule: the default one, with whatever parts were given
+    pub fn schedule(&self) -> AnnealSchedule {
+        let default = AnnealSchedule::default();
+        AnnealSchedule {
+            temp: self.temp.unwrap_or(default.temp),
+            cooling: self.cooling.unwrap_or(default.cooling),
+            iterations: self.iterations.unwrap_or(default.iterations),
+            restarts: self.restarts.unwrap_or(default.restarts),
+        }
+    }
+
+    fn tunes_schedule(&self) -> bool {
+        self.temp.is_some()
+            || self.cooling.is_some()
+            || self.iterations.is_some()
+            || self.restarts.is_some()
+    }
+}
+
+/// Hill count or `anneal` given as a positional argument, the way older versions took it
+#[derive(Debug)]
+enum Legacy {
+    Hills(usize),
+    Anneal,
+}
+
+impl FromStr for Legacy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "anneal" => Ok(Legacy::Anneal),
+            _ => s
+                .parse()
+                .map(Legacy::Hills)
+                .map_err(|_| format!("expected a number of hills or `anneal`, got {}", s)),
+        }
+    }
+}
+
+// Where the language data comes from
+#[derive(Debug, StructOpt)]
+pub struct Data {
+    /// Word list, one word per line; defaults to the copy of words_alpha.txt built into the program
+    #[structopt(long, env = "SUB_CIPHER_DICT", parse(from_os_str))]
+    pub dict: Option<PathBuf>,
+    /// N-gram table, one `NGRAM COUNT` per line; defaults to the copy of quadgrams.txt built into the program
+    #[structopt(long, env = "SUB_CIPHER_NGRAMS", parse(from_os_str))]
+    pub ngrams: Option<PathBuf>,
+    /// Add this much to the score of a candidate plaintext for every letter in a dictionary word, on top of the
+    /// n-gram log probability; try 1. Helps on short texts, but makes cracking slower.
+    #[structopt(long)]
+    pub word_weight: Option<f64>,
+    /// `words` to only count n-grams within the words of the ciphertext, `letters` to let them run across spaces.
+    /// Texts without word breaks, or in groups of the same length, are always scored as letters, with no
+    /// --word-weight. [default: letters]
+    #[structopt(long)]
+    pub scoring: Option<Scoring>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Output {
+    /// Output format: text or json
+    #[structopt(long, default_value = "text", parse(try_from_str = Format::parse))]
+    pub format: Format,
+}
+
+/// Cipher forced with --cipher
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cipher {
+    Caesar,
+    Affine,
+    Vigenere,
+    Transposition,
+    Playfair,
+    Hill,
+    Homophonic,
+    Substitution,
+}
+
+impl FromStr for Cipher {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "caesar" => Ok(Cipher::Caesar),
+            "affine" => Ok(Cipher::Affine),
+            "vigenere" => Ok(Cipher::Vigenere),
+            "transposition" | "columnar" => Ok(Cipher::Transposition),
+            "playfair" => Ok(Cipher::Playfair),
+            "hill" => Ok(Cipher::Hill),
+            "homophonic" => Ok(Cipher::Homophonic),
+            "substitution" | "simple" => Ok(Cipher::Substitution),
+            _ => Err(format!(
+                "unknown cipher: {}; expected caesar, affine, vigenere, transposition, playfair, hill, \
+                 homophonic or substitution",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Command {
+        Command::from_iter_safe(std::iter::once("prog").chain(args.iter().cloned())).unwrap()
+    }
+
+    #[test]
+    fn test_legacy_hills() {
+        match parse(&["decode", "samples/sample3.txt", "1500"]) {
+            Command::Decode { search, .. } => {
+                assert_eq!(search.stop_rule(), Some(StopRule::hills(1500)))
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+        match parse(&["decode", "samples/sample3.txt", "anneal"]) {
+            Command::Decode { search, .. } => {
+                assert_eq!(search.stop_rule(), None);
+                assert!(search.is_explicit());
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+        match parse(&["decode", "samples/sample3.txt", "--seed", "1"]) {
+            Command::Decode { search, .. } => assert!(!search.is_explicit()),
+            cmd => panic!("{:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn test_options() {
+        match parse(&[
+            "decode", "--hills", "20", "--cipher", "caesar", "--format", "json",
+        ]) {
+            Command::Decode {
+                input,
+                search,
+                cipher,
+                output,
+                ..
+            } => {
+                assert_eq!(input.file, None);
+                assert_eq!(search.stop_rule(), Some(StopRule::hills(20)));
+                assert_eq!(cipher, Some(Cipher::Caesar));
+                assert_eq!(output.format, Format::Json);
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+        assert!(Command::from_iter_safe(&["prog", "decode", "f.txt", "lots"]).is_err());
+
+        match parse(&[
+            "decode",
+            "--crib",
+            "attack at dawn",
+            "--crib",
+            "the enemy@3",
+        ]) {
+            Command::Decode { cribs, .. } => assert_eq!(
+                cribs,
+                vec![
+                    "attack at dawn".parse().unwrap(),
+                    Crib {
+                        text: "theenemy".to_owned(),
+                        position: Some(3)
+                    }
+                ]
+            ),
+            cmd => panic!("{:?}", cmd),
+        }
+        match parse(&["decode", "--crib", "dawn"]) {
+            Command::Decode { search, .. } => {
+                assert_eq!(search.crib_hills(), Ok(DEFAULT_CRIB_HILLS))
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+        match parse(&["decode", "--crib", "dawn", "--confirm", "3"]) {
+            Command::Decode { search, .. } => assert!(search.crib_hills().is_err()),
+            cmd => panic!("{:?}", cmd),
+        }
+
+        match parse(&["decode", "--temp", "0.1", "--restarts", "2"]) {
+            Command::Decode { search, .. } => {
+                assert_eq!(search.stop_rule(), None);
+                let schedule = search.schedule();
+                assert_eq!((schedule.temp, schedule.restarts), (0.1, 2));
+                assert_eq!(schedule.iterations, AnnealSchedule::default().iterations);
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+        assert!(
+            Command::from_iter_safe(&["prog", "decode", "--hills", "5", "--temp", "0.1"]).is_err()
+        );
+        match parse(&["decode", "f.txt", "50", "--cooling", "0.999"]) {
+            Command::Decode { search, .. } => assert!(search.check().is_err()),
+            cmd => panic!("{:?}", cmd),
+        }
+
+        match parse(&["decode", "--confirm", "4", "--budget", "1.5"]) {
+            Command::Decode { search, .. } => {
+                assert_eq!(search.check(), Ok(()));
+                assert_eq!(
+                    search.stop_rule(),
+                    Some(StopRule {
+                        max_hills: DEFAULT_ADAPTIVE_HILLS,
+                        confirmations: Some(4),
+                        budget: Some(Duration::from_millis(1500)),
+                    })
+                )
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+        for budget in [
+            "--budget=-1",
+            "--budget=0",
+            "--budget=nan",
+            "--budget=inf",
+            "--budget=1e300",
+        ]
+        .iter()
+        {
+            match parse(&["decode", budget]) {
+                Command::Decode { search, .. } => assert!(search.check().is_err()),
+                cmd => panic!("{:?}", cmd),
+            }
+        }
+    }
+}
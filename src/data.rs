@@ -0,0 +1,68 @@
+//! Language data: the word list and n-gram table, read from a file or from the compressed copies embedded
+//! by `build.rs`
+
+use std::{
+    fs,
+    io::{self, prelude::*, BufReader},
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+
+use crate::ngram::NGram;
+
+const WORDS_GZ: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/words_alpha.txt.gz"));
+const QUADGRAMS_GZ: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/quadgrams.txt.gz"));
+
+/// Read the word list at `path`, or the embedded one if there is no path. `Dict` borrows from the result.
+pub fn read_words(path: Option<&Path>) -> io::Result<String> {
+    match path {
+        Some(path) => fs::read_to_string(path).map_err(|e| with_path(e, path)),
+        None => {
+            let mut words = String::new();
+            GzDecoder::new(WORDS_GZ).read_to_string(&mut words)?;
+            Ok(words)
+        }
+    }
+}
+
+/// Load the n-gram table at `path`, inferring n, or the embedded quadgrams if there is no path
+pub fn load_ngrams(path: Option<&Path>) -> io::Result<NGram> {
+    match path {
+        Some(path) => NGram::from_file(path, None).map_err(|e| with_path(e, path)),
+        None => embedded_quadgrams(),
+    }
+}
+
+pub fn embedded_quadgrams() -> io::Result<NGram> {
+    NGram::from_reader(BufReader::new(GzDecoder::new(QUADGRAMS_GZ)), Some(4))
+}
+
+/// Prefix an io error with the file it happened on
+pub fn with_path(e: io::Error, path: &Path) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_matches_files() {
+        assert_eq!(
+            read_words(None).unwrap(),
+            fs::read_to_string("words_alpha.txt").unwrap()
+        );
+
+        let embedded = embedded_quadgrams().unwrap();
+        let file = NGram::from_file("quadgrams.txt", Some(4)).unwrap();
+        let text: Vec<char> = "attackthewallatdawn".chars().collect();
+        assert_eq!(embedded.score_chars(&text), file.score_chars(&text));
+    }
+
+    #[test]
+    fn test_missing_file_names_path() {
+        let err = read_words(Some(Path::new("no/such/words.txt"))).unwrap_err();
+        assert!(err.to_string().starts_with("no/such/words.txt: "));
+    }
+}
@@ -14,9 +14,19 @@ impl<'a> Dict<'a> {
     pub fn new(words: HashSet<&'a str>) -> Self {
         Self { words }
     }
+
+    /// Every word, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.words.iter().copied()
+    }
 }
 
 impl Dict<'_> {
+    /// Assumes word is in lower alphabets
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word)
+    }
+
     #[allow(dead_code)]
     /// Assumes that every node of vec contains a word with only lower alphabets
     pub fn contains_every_word(&self, list: &[String]) -> bool {
@@ -0,0 +1,217 @@
+#![allow(dead_code)]
+
+use std::str::FromStr;
+
+use crate::{alpha, dict::Dict, ngram::NGram};
+
+/// Words longer than this never count as dictionary words
+const MAX_WORD_LEN: usize = 32;
+
+/// Lowest mean log10 probability per letter of an n-gram for `Fitness::is_plausible()`; English text scores
+/// about -1.1 with quadgrams, gibberish -1.5 or less
+const MIN_PLAUSIBLE_PER_LETTER: f64 = -1.3;
+
+/// Whether n-grams are counted across the spaces of a text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scoring {
+    /// Only within words, so words shorter than n score nothing
+    Words,
+    /// The letters are one stream, the way the n-gram tables are counted; a must for texts without word breaks
+    /// or in fixed size groups, and usually better on spaced texts too
+    Letters,
+}
+
+impl FromStr for Scoring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "words" => Ok(Scoring::Words),
+            "letters" => Ok(Scoring::Letters),
+            _ => Err(format!("unknown scoring: {}; expected words or letters", s)),
+        }
+    }
+}
+
+/// How plaintext candidates are scored by the crackers; higher is better
+///
+/// Always the n-gram log probability. With a dictionary, every letter of a plaintext word found in it also adds
+/// `word_weight`, which keeps short texts from being won by gibberish that happens to have likely n-grams; only
+/// give one for texts whose spaces are word breaks. Crackers that throw away word boundaries, like columnar
+/// transposition, only get the n-gram part.
+#[derive(Clone, Copy)]
+pub struct Fitness<'a> {
+    ngram: &'a NGram,
+    dict: Option<&'a Dict<'a>>,
+    /// Bonus per letter of a dictionary word, in the same log10 units as the n-gram score
+    word_weight: f64,
+    scoring: Scoring,
+}
+
+impl<'a> Fitness<'a> {
+    /// N-gram score only
+    pub fn new(ngram: &'a NGram) -> Self {
+        Self {
+            ngram,
+            dict: None,
+            word_weight: 0.,
+            scoring: Scoring::Letters,
+        }
+    }
+
+    /// N-gram score plus `word_weight` for every letter in a dictionary word
+    pub fn with_dict(ngram: &'a NGram, dict: &'a Dict<'a>, word_weight: f64) -> Self {
+        Self {
+            ngram,
+            dict: Some(dict),
+            word_weight,
+            scoring: Scoring::Letters,
+        }
+    }
+
+    /// Same fitness, counting n-grams as `scoring` says; the default is `Scoring::Letters`
+    pub fn with_scoring(self, scoring: Scoring) -> Self {
+        Self { scoring, ..self }
+    }
+
+    pub fn scoring(&self) -> Scoring {
+        self.scoring
+    }
+
+    pub fn ngram(&self) -> &'a NGram {
+        self.ngram
+    }
+
+    /// Score letter positions (0 for 'a') with no word boundaries
+    pub fn score_letters(&self, letters: &[u8]) -> f64 {
+        self.ngram.score_letters(letters)
+    }
+
+    /// Score letter positions that split into words of `word_lens` letters each, in order.
+    /// `word_lens` is ignored with `Scoring::Letters` and no dictionary.
+    pub fn score_words<I: IntoIterator<Item = usize>>(&self, letters: &[u8], word_lens: I) -> f64 {
+        if self.scoring == Scoring::Letters && self.dict.is_none() {
+            return self.ngram.score_letters(letters);
+        }
+
+        let mut score = match self.scoring {
+            Scoring::Letters => self.ngram.score_letters(letters),
+            Scoring::Words => 0.,
+        };
+        let mut dict_count = 0;
+        let mut start = 0;
+        for len in word_lens {
+            let end = (start + len).min(letters.len());
+            let word = &letters[start..end];
+            start = end;
+
+            if self.scoring == Scoring::Words {
+                score += self.ngram.score_letters(word);
+            }
+            if let Some(dict) = self.dict {
+                if is_dict_word(dict, word) {
+                    dict_count += word.len();
+                }
+            }
+        }
+        score + self.word_weight * dict_count as f64
+    }
+
+    /// Whether the n-grams of text are likely enough on average for it to be English, so that a cracker can
+    /// tell a wrong guess of the cipher family from a solution. Case and chars other than ascii letters are
+    /// ignored; text with fewer letters than an n-gram is never plausible.
+    pub fn is_plausible(&self, text: &str) -> bool {
+        let letters: Vec<u8> = text
+            .chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .collect();
+        let n = self.ngram.n();
+        if letters.len() < n {
+            return false;
+        }
+        let mean = self.ngram.score_letters(&letters) / (letters.len() - n + 1) as f64;
+        mean >= MIN_PLAUSIBLE_PER_LETTER * n as f64
+    }
+
+    /// Score text, using whitespace as word boundaries; case and chars other than ascii letters are ignored
+    pub fn score_text(&self, text: &str) -> f64 {
+        let letters: Vec<u8> = text
+            .chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .collect();
+        let word_lens = text
+            .split_whitespace()
+            .map(|word| word.chars().filter(char::is_ascii_alphabetic).count());
+        self.score_words(&letters, word_lens)
+    }
+}
+
+/// Whether the letter positions of `word` spell a dictionary word
+fn is_dict_word(dict: &Dict, word: &[u8]) -> bool {
+    if word.is_empty() || word.len() > MAX_WORD_LEN {
+        return false;
+    }
+    let mut buf = [0u8; MAX_WORD_LEN];
+    for (b, letter) in buf.iter_mut().zip(word) {
+        *b = b'a' + letter;
+    }
+    // only ascii letters were written
+    dict.contains(std::str::from_utf8(&buf[..word.len()]).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_bonus() {
+        let quads = NGram::load_default_quad().unwrap();
+        let dict = Dict::new(["the", "cat", "sat"].iter().cloned().collect());
+
+        let plain = Fitness::new(&quads);
+        let words = Fitness::with_dict(&quads, &dict, 1.);
+        // only the letters of whole dictionary words count
+        assert_eq!(
+            words.score_text("the cat sat"),
+            plain.score_text("the cat sat") + 9.
+        );
+        assert_eq!(
+            words.score_text("thec at sat"),
+            plain.score_text("thec at sat") + 3.
+        );
+    }
+
+    #[test]
+    fn test_scoring() {
+        let quads = NGram::load_default_quad().unwrap();
+        let dict = Dict::new(["there", "cat", "sat"].iter().cloned().collect());
+        let letters = Fitness::new(&quads);
+        assert_eq!(
+            letters.score_text("the cat sat"),
+            quads.score_letters(&[19, 7, 4, 2, 0, 19, 18, 0, 19])
+        );
+
+        // n-grams don't cross the spaces, so 3 letter words score nothing
+        let words = letters.with_scoring(Scoring::Words);
+        assert_eq!(words.score_text("the cat sat"), 0.);
+        let words = Fitness::with_dict(&quads, &dict, 1.).with_scoring(Scoring::Words);
+        assert_eq!(
+            words.score_text("there cat sat"),
+            quads.score_letters(&[19, 7, 4, 17, 4]) + 11.
+        );
+
+        assert_eq!("words".parse(), Ok(Scoring::Words));
+        assert!("spaces".parse::<Scoring>().is_err());
+    }
+
+    #[test]
+    fn test_is_plausible() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        assert!(fitness.is_plausible("The quick brown fox jumps over the lazy dog."));
+        assert!(fitness.is_plausible("lifeisalwaysarichandsteadyway"));
+        assert!(!fitness.is_plausible("UIFRVJDLCSPXOGPYKVNQTPWFSUIFMBZAEPH"));
+        assert!(!fitness.is_plausible("xjiu cnpyypm, irh xgz uypxgw xbozu"));
+        assert!(!fitness.is_plausible("the"));
+    }
+}
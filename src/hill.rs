@@ -0,0 +1,404 @@
+#![allow(dead_code)]
+
+use std::fmt;
+
+use crate::{alpha, fitness::Fitness, simp_sub::Crib};
+
+/// Decryption rows kept for pairing up in `Hill::crack()`, by how English the letters they give look
+const TOP_ROWS: usize = 40;
+
+/// Fewest letters `Hill::crack()` will work with
+const MIN_LETTERS: usize = 20;
+
+/// Hill cipher: the letters are encrypted in blocks of n, each block as a column vector multiplied by an n x n key
+/// matrix mod 26. Only 2x2 and 3x3 keys are supported.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Hill {
+    size: usize,
+    /// Key matrix, row by row
+    key: Vec<u8>,
+    /// Inverse of `key` mod 26, for decryption
+    inverse: Vec<u8>,
+}
+
+impl fmt::Debug for Hill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Hill---")?;
+        for row in self.rows() {
+            writeln!(f, "{:?}", row)?;
+        }
+        Ok(())
+    }
+}
+
+impl Hill {
+    /// Key from a 2x2 or 3x3 matrix, row by row; it must be invertible mod 26, and the numbers are taken mod 26
+    pub fn new(matrix: &[i32]) -> Result<Self, &'static str> {
+        let size = match matrix.len() {
+            4 => 2,
+            9 => 3,
+            _ => return Err("Error creating a key for Hill: matrix must be 2x2 or 3x3"),
+        };
+        let key: Vec<u8> = matrix.iter().map(|x| x.rem_euclid(26) as u8).collect();
+        let inverse = inverse(&key, size)
+            .ok_or("Error creating a key for Hill: matrix must be invertible mod 26")?;
+        Ok(Self { size, key, inverse })
+    }
+
+    /// Key whose matrix is the letters of `keyword`, row by row: "gybnqkurp" for a 3x3 key
+    pub fn from_keyword(keyword: &str) -> Result<Self, &'static str> {
+        let matrix: Vec<i32> = keyword
+            .chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .map(i32::from)
+            .collect();
+        Self::new(&matrix)
+    }
+
+    fn from_inverse(inverse: Vec<u8>, size: usize) -> Option<Self> {
+        let key = self::inverse(&inverse, size)?;
+        Some(Self { size, key, inverse })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The key matrix, row by row
+    pub fn rows(&self) -> Vec<Vec<u8>> {
+        self.key.chunks(self.size).map(<[u8]>::to_vec).collect()
+    }
+
+    /// Encrypt text, keeping case and leaving chars other than ascii letters unchanged. X is appended to fill
+    /// the last block.
+    pub fn encrypt(&self, text: &str) -> String {
+        let count = text.chars().filter(char::is_ascii_alphabetic).count();
+        let padding = (self.size - count % self.size) % self.size;
+        let padded: String = text.chars().chain((0..padding).map(|_| 'x')).collect();
+        transform(&self.key, self.size, &padded)
+    }
+
+    /// Decrypt text, keeping case and leaving chars other than ascii letters unchanged; padding is left in.
+    /// Assumes `check()` passes on text.
+    pub fn decrypt(&self, text: &str) -> String {
+        transform(&self.inverse, self.size, text)
+    }
+
+    /// Reject text that can't be Hill ciphertext with a key of this size: the letters must fill whole blocks
+    pub fn check(text: &str, size: usize) -> Result<(), &'static str> {
+        let count = text.chars().filter(char::is_ascii_alphabetic).count();
+        if count == 0 || !count.is_multiple_of(size) {
+            return Err("not hill: the number of letters must be a multiple of the matrix size");
+        }
+        Ok(())
+    }
+
+    /// Solve a key of `size` from known plaintext, and return it with the score of the decryption
+    ///
+    /// Wherever the crib may go, n blocks of it with an invertible plaintext matrix P and their ciphertext C give
+    /// the key K = C * P^-1, which must also encrypt the rest of the crib. So the crib needs at least n * n
+    /// letters starting on a block boundary, or n - 1 more anywhere, and usually a few more in case the first
+    /// blocks are not invertible. The placement whose key decrypts best wins.
+    pub fn from_crib(
+        text: &str,
+        crib: &Crib,
+        size: usize,
+        fitness: &Fitness,
+    ) -> Result<(Self, f64), &'static str> {
+        Self::check(text, size)?;
+        let letters = only_letters(text);
+        let plain: Vec<u8> = crib.text.bytes().map(|b| b - b'a').collect();
+        if plain.len() > letters.len() {
+            return Err("the crib is longer than the ciphertext");
+        }
+        let positions = match crib.position {
+            Some(position) if position + plain.len() <= letters.len() => position..position + 1,
+            Some(_) => return Err("the crib does not fit at its position"),
+            None => 0..letters.len() - plain.len() + 1,
+        };
+
+        let mut best: Option<(Self, f64)> = None;
+        let mut out = vec![];
+        for position in positions {
+            let key = match solve_crib(&letters[position..], &plain, position % size, size) {
+                Some(key) => key,
+                None => continue,
+            };
+            multiply_blocks(&key.inverse, size, &letters, &mut out);
+            let score = fitness.score_letters(&out);
+            // ties go to the earlier position
+            if best.as_ref().is_none_or(|best| score > best.1) {
+                best = Some((key, score));
+            }
+        }
+        best.ok_or("the crib gives no invertible key at any position")
+    }
+
+    /// Crack text as a 2x2 Hill cipher with no known plaintext, and return the key with its score
+    ///
+    /// Each row of the decryption matrix gives every other plaintext letter on its own, so the 26 * 26 rows are
+    /// first ranked by how close the letters they give are to English letter frequencies. Only the best rows
+    /// are then paired up into invertible matrices, whose decryptions are scored with the n-gram part of
+    /// `fitness`.
+    pub fn crack(text: &str, fitness: &Fitness) -> Result<(Self, f64), &'static str> {
+        Self::check(text, 2)?;
+        let letters = only_letters(text);
+        if letters.len() < MIN_LETTERS {
+            return Err("too few letters to crack as hill");
+        }
+
+        let mut rows: Vec<([u8; 2], f64)> = (0..26u8)
+            .flat_map(|a| (0..26u8).map(move |b| [a, b]))
+            // such a row can't be part of an invertible matrix
+            .filter(|row| row.iter().any(|x| x % 2 == 1) && row.iter().any(|x| x % 13 != 0))
+            .map(|row| {
+                let score = letters
+                    .chunks_exact(2)
+                    .map(|block| {
+                        let pos = (row[0] as usize * block[0] as usize
+                            + row[1] as usize * block[1] as usize)
+                            % 26;
+                        alpha::ENGLISH_FREQ[pos].ln()
+                    })
+                    .sum();
+                (row, score)
+            })
+            .collect();
+        // stable, so ties keep the smaller rows first
+        rows.sort_by(|x, y| y.1.partial_cmp(&x.1).unwrap());
+        rows.truncate(TOP_ROWS);
+
+        let mut best: Option<(Self, f64)> = None;
+        let mut out = vec![];
+        for (first, _) in rows.iter() {
+            for (second, _) in rows.iter() {
+                let inverse = vec![first[0], first[1], second[0], second[1]];
+                let key = match Self::from_inverse(inverse, 2) {
+                    Some(key) => key,
+                    None => continue,
+                };
+                multiply_blocks(&key.inverse, 2, &letters, &mut out);
+                let score = fitness.score_letters(&out);
+                if best.as_ref().is_none_or(|best| score > best.1) {
+                    best = Some((key, score));
+                }
+            }
+        }
+        best.ok_or("no pair of likely rows makes an invertible matrix")
+    }
+}
+
+/// Key that encrypts `plain` to the start of `crypt`, where `plain` starts `offset` letters into a block, if n
+/// of its whole blocks are invertible and the key fits all of them
+fn solve_crib(crypt: &[u8], plain: &[u8], offset: usize, size: usize) -> Option<Hill> {
+    let skip = (size - offset) % size;
+    if plain.len() < skip {
+        return None;
+    }
+    let blocks: Vec<(&[u8], &[u8])> = plain[skip..]
+        .chunks_exact(size)
+        .zip(crypt[skip..].chunks_exact(size))
+        .collect();
+
+    for window in blocks.windows(size) {
+        // blocks as columns; K * P = C
+        let p: Vec<u8> = (0..size * size)
+            .map(|i| window[i % size].0[i / size])
+            .collect();
+        let c: Vec<u8> = (0..size * size)
+            .map(|i| window[i % size].1[i / size])
+            .collect();
+        let p_inv = match inverse(&p, size) {
+            Some(p_inv) => p_inv,
+            None => continue,
+        };
+        let key = Hill::new(
+            &multiply(&c, &p_inv, size)
+                .iter()
+                .map(|x| i32::from(*x))
+                .collect::<Vec<_>>(),
+        )
+        .ok()?;
+        let mut out = vec![];
+        let fits = blocks.iter().all(|(plain, crypt)| {
+            multiply_blocks(&key.key, size, plain, &mut out);
+            out == *crypt
+        });
+        return if fits { Some(key) } else { None };
+    }
+    None
+}
+
+/// Letter positions (0 for 'a') of the ascii letters of text
+fn only_letters(text: &str) -> Vec<u8> {
+    text.chars()
+        .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+        .collect()
+}
+
+/// Multiply every block of `size` letters by `matrix` into `out`; a partial last block is dropped
+fn multiply_blocks(matrix: &[u8], size: usize, letters: &[u8], out: &mut Vec<u8>) {
+    out.clear();
+    for block in letters.chunks_exact(size) {
+        for row in matrix.chunks(size) {
+            let sum: usize = row
+                .iter()
+                .zip(block)
+                .map(|(a, b)| *a as usize * *b as usize)
+                .sum();
+            out.push((sum % 26) as u8);
+        }
+    }
+}
+
+/// Replace the letters of text by the blocks multiplied by `matrix`, keeping case and the other chars
+fn transform(matrix: &[u8], size: usize, text: &str) -> String {
+    let mut out = vec![];
+    multiply_blocks(matrix, size, &only_letters(text), &mut out);
+    let mut out = out.into_iter();
+    text.chars()
+        .filter_map(|c| {
+            if c.is_ascii_alphabetic() {
+                out.next()
+                    .map(|pos| alpha::match_case((b'a' + pos) as char, c))
+            } else {
+                Some(c)
+            }
+        })
+        .collect()
+}
+
+/// Product of two square matrices of `size`, row by row, mod 26
+pub fn multiply(a: &[u8], b: &[u8], size: usize) -> Vec<u8> {
+    (0..size * size)
+        .map(|i| {
+            let (row, col) = (i / size, i % size);
+            let sum: usize = (0..size)
+                .map(|k| a[row * size + k] as usize * b[k * size + col] as usize)
+                .sum();
+            (sum % 26) as u8
+        })
+        .collect()
+}
+
+/// Determinant of a square matrix of `size`, row by row, mod 26
+pub fn determinant(matrix: &[u8], size: usize) -> u8 {
+    if size == 1 {
+        return matrix[0];
+    }
+    let sum: i32 = (0..size)
+        .map(|col| {
+            let term =
+                matrix[col] as i32 * determinant(&minor(matrix, size, 0, col), size - 1) as i32;
+            if col % 2 == 0 {
+                term
+            } else {
+                -term
+            }
+        })
+        .sum();
+    sum.rem_euclid(26) as u8
+}
+
+/// Inverse of x mod 26, if x is coprime with 26
+pub fn mod_inverse(x: u8) -> Option<u8> {
+    (1..26).find(|inv| (x as u32 * *inv as u32) % 26 == 1)
+}
+
+/// Inverse of a square matrix of `size` mod 26, row by row; it exists when the determinant is coprime with 26
+pub fn inverse(matrix: &[u8], size: usize) -> Option<Vec<u8>> {
+    let det_inv = mod_inverse(determinant(matrix, size))? as i32;
+    if size == 1 {
+        return Some(vec![det_inv as u8]);
+    }
+    // the adjugate: the transposed matrix of cofactors
+    Some(
+        (0..size * size)
+            .map(|i| {
+                let (row, col) = (i / size, i % size);
+                let cofactor = determinant(&minor(matrix, size, col, row), size - 1) as i32;
+                let sign = if (row + col) % 2 == 0 { 1 } else { -1 };
+                (sign * cofactor * det_inv).rem_euclid(26) as u8
+            })
+            .collect(),
+    )
+}
+
+/// The matrix without one row and column
+fn minor(matrix: &[u8], size: usize, row: usize, col: usize) -> Vec<u8> {
+    (0..size * size)
+        .filter(|i| i / size != row && i % size != col)
+        .map(|i| matrix[i])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ngram::NGram;
+    use crate::test_text::PLAIN;
+
+    #[test]
+    fn test_inverse() {
+        let key = [3, 3, 2, 5];
+        assert_eq!(determinant(&key, 2), 9);
+        assert_eq!(inverse(&key, 2), Some(vec![15, 17, 20, 9]));
+        assert_eq!(multiply(&key, &[15, 17, 20, 9], 2), vec![1, 0, 0, 1]);
+        // determinant 2 and 13 have no inverse
+        assert_eq!(inverse(&[1, 1, 1, 3], 2), None);
+        assert_eq!(inverse(&[13, 0, 0, 1], 2), None);
+
+        let key = Hill::from_keyword("gybnqkurp").unwrap();
+        assert_eq!(determinant(&key.key, 3), 25);
+        assert_eq!(key.inverse, vec![8, 5, 10, 21, 8, 21, 21, 12, 8]);
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = Hill::from_keyword("gybnqkurp").unwrap();
+        assert_eq!(key.encrypt("act"), "poh");
+        assert_eq!(key.encrypt("Cat!"), "Fin!");
+        assert_eq!(key.decrypt("Fin!"), "Cat!");
+
+        let key = Hill::new(&[3, 3, 2, 5]).unwrap();
+        assert_eq!(key.encrypt("Help"), "Hiat");
+        assert_eq!(key.encrypt("hel"), "hiyh");
+        assert_eq!(key.decrypt("hiyh"), "helx");
+
+        assert!(Hill::new(&[1, 2, 3]).is_err());
+        assert!(Hill::new(&[2, 0, 0, 1]).is_err());
+        assert!(Hill::check("abc", 2).is_err());
+        assert!(Hill::check("ab cd", 2).is_ok());
+    }
+
+    #[test]
+    fn test_from_crib() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let key = Hill::from_keyword("gybnqkurp").unwrap();
+        let text = key.encrypt(PLAIN);
+
+        // 9 letters are enough at a block boundary, but 11 are needed anywhere
+        let crib: Crib = "substitut@9".parse().unwrap();
+        assert_eq!(Hill::from_crib(&text, &crib, 3, &fitness).unwrap().0, key);
+        let crib: Crib = "esubstituti".parse().unwrap();
+        assert_eq!(Hill::from_crib(&text, &crib, 3, &fitness).unwrap().0, key);
+        let crib: Crib = "substitut@10".parse().unwrap();
+        assert!(Hill::from_crib(&text, &crib, 3, &fitness).is_err());
+        // the first blocks, "the sim ple", are not invertible
+        let crib: Crib = "thesimple@0".parse().unwrap();
+        assert!(Hill::from_crib(&text, &crib, 3, &fitness).is_err());
+    }
+
+    #[test]
+    fn test_crack() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let key = Hill::new(&[5, 17, 4, 15]).unwrap();
+        let text = key.encrypt(PLAIN);
+
+        let (cracked, _score) = Hill::crack(&text, &fitness).unwrap();
+        assert_eq!(cracked, key);
+        assert!(Hill::crack("abcd", &fitness).is_err());
+    }
+}
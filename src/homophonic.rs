@@ -0,0 +1,386 @@
+#![allow(dead_code)]
+
+//! Homophonic substitution: every plain letter has one or more cipher symbols of its own, so the common letters
+//! can be spread over several symbols and letter frequencies give little away. Symbols are arbitrary tokens,
+//! like numbers, separated by whitespace or commas.
+
+use std::{collections::HashMap, fmt};
+
+use rand::{rngs::StdRng, Rng};
+use rayon::prelude::*;
+
+use crate::{
+    alpha,
+    fitness::Fitness,
+    simp_sub::{self, AnnealSchedule},
+};
+
+/// Annealing schedule of `Homophonic::crack()`
+pub const SCHEDULE: AnnealSchedule = AnnealSchedule {
+    temp: 0.04,
+    cooling: 0.99995,
+    iterations: 200_000,
+    restarts: 4,
+};
+
+/// Out of 16 moves, how many give two symbols each other's letters instead of giving one symbol a new letter
+const SWAP_MOVES: u32 = 3;
+
+/// Fewest symbols of text without letters for `decode` to crack it as homophonic unasked; shorter texts do not
+/// have enough n-grams for annealing to tell English from gibberish
+pub const MIN_AUTO_SYMBOLS: usize = 100;
+
+/// Weight of the letter frequency term of `score()`
+const FREQ_WEIGHT: f64 = 6.;
+
+/// Symbol standing for a plain letter no symbol is known for
+const UNKNOWN: char = '?';
+
+/// Ciphertext split into symbols
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbols {
+    /// Distinct symbols, in order of first appearance
+    pub symbols: Vec<String>,
+    /// The ciphertext, as indices into `symbols`
+    pub text: Vec<usize>,
+}
+
+impl Symbols {
+    /// Split text on whitespace and commas
+    pub fn parse(text: &str) -> Self {
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        let mut symbols = vec![];
+        let text = text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                *index.entry(token).or_insert_with(|| {
+                    symbols.push(token.to_owned());
+                    symbols.len() - 1
+                })
+            })
+            .collect();
+        Self { symbols, text }
+    }
+
+    /// Positions in the text of every symbol
+    fn positions(&self) -> Vec<Vec<usize>> {
+        let mut positions = vec![vec![]; self.symbols.len()];
+        for (pos, symbol) in self.text.iter().enumerate() {
+            positions[*symbol].push(pos);
+        }
+        positions
+    }
+}
+
+/// Key of a homophonic cipher: the plain letter of every cipher symbol
+#[derive(Clone, PartialEq, Eq)]
+pub struct Homophonic {
+    symbols: Vec<String>,
+    /// Plain letter (0 for 'a') of each of `symbols`
+    letters: Vec<u8>,
+}
+
+impl fmt::Debug for Homophonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Homophonic---")?;
+        for (letter, symbols) in self.homophones() {
+            writeln!(f, "{}: {}", letter, symbols.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Homophonic {
+    /// Key where `symbols[i]` stands for plain letter `letters[i]`; symbols must be distinct and must not
+    /// contain whitespace or commas, and letters must be ascii letters
+    pub fn new(symbols: &[&str], letters: &str) -> Result<Self, &'static str> {
+        let letters: Option<Vec<u8>> = letters
+            .chars()
+            .map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .collect();
+        let letters =
+            letters.ok_or("Error creating a key for Homophonic: letters must be ascii letters")?;
+        if letters.len() != symbols.len() {
+            return Err("Error creating a key for Homophonic: need one letter per symbol");
+        }
+        let parsed = Symbols::parse(&symbols.join(" "));
+        if parsed.symbols.len() != symbols.len() || parsed.symbols != symbols {
+            return Err(
+                "Error creating a key for Homophonic: symbols must be distinct, without whitespace or commas",
+            );
+        }
+        Ok(Self {
+            symbols: parsed.symbols,
+            letters,
+        })
+    }
+
+    /// Cipher symbols of every plain letter that has any, in alphabetical order
+    pub fn homophones(&self) -> Vec<(char, Vec<&str>)> {
+        let mut homophones = vec![vec![]; 26];
+        for (symbol, letter) in self.symbols.iter().zip(&self.letters) {
+            homophones[*letter as usize].push(symbol.as_str());
+        }
+        homophones
+            .into_iter()
+            .enumerate()
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(letter, symbols)| ((b'a' + letter as u8) as char, symbols))
+            .collect()
+    }
+
+    /// Encrypt the letters of text as symbols separated by spaces, using the symbols of each letter in turn.
+    /// Fails if a letter has no symbol.
+    pub fn encrypt(&self, text: &str) -> Result<String, &'static str> {
+        let mut homophones = vec![vec![]; 26];
+        for (symbol, letter) in self.symbols.iter().zip(&self.letters) {
+            homophones[*letter as usize].push(symbol.as_str());
+        }
+        let mut used = [0usize; 26];
+        text.chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .map(|pos| {
+                let symbols = &homophones[pos as usize];
+                if symbols.is_empty() {
+                    return Err("a letter of the text has no symbol");
+                }
+                used[pos as usize] += 1;
+                Ok(symbols[(used[pos as usize] - 1) % symbols.len()])
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|symbols| symbols.join(" "))
+    }
+
+    /// Decrypt the symbols of text to lowercase letters, with `?` for symbols not in the key
+    pub fn decrypt(&self, text: &str) -> String {
+        let letters: HashMap<&str, u8> = self
+            .symbols
+            .iter()
+            .map(String::as_str)
+            .zip(self.letters.iter().cloned())
+            .collect();
+        let parsed = Symbols::parse(text);
+        parsed
+            .text
+            .iter()
+            .map(
+                |symbol| match letters.get(parsed.symbols[*symbol].as_str()) {
+                    Some(pos) => (b'a' + pos) as char,
+                    None => UNKNOWN,
+                },
+            )
+            .collect()
+    }
+
+    /// Crack text by simulated annealing over the letters of the symbols, from `schedule.restarts` starts, and
+    /// return the best key with the n-gram score of its decryption, unless that is not plausibly English. Only the
+    /// n-gram part of `fitness` applies.
+    ///
+    /// Starting letters, and the new letters tried for a symbol, are drawn by English letter frequency, so a
+    /// symbol is far more likely to become an e than a z; the number of symbols per letter is left free. Each
+    /// restart has its own rng derived from `seed`, so the same seed always gives the same key.
+    pub fn crack(
+        text: &str,
+        fitness: &Fitness,
+        schedule: &AnnealSchedule,
+        seed: u64,
+    ) -> Result<(Self, f64), &'static str> {
+        let parsed = Symbols::parse(text);
+        if parsed.text.is_empty() {
+            return Err("no symbols to crack");
+        }
+        let positions = parsed.positions();
+
+        let restarts: Vec<(Vec<u8>, f64)> = simp_sub::worker_rngs(seed, schedule.restarts.max(1))
+            .into_par_iter()
+            .map(|mut rng| anneal(&parsed, &positions, fitness, schedule, &mut rng))
+            .collect();
+        // ties go to the earlier restart
+        let (letters, _) = restarts
+            .into_iter()
+            .fold(None, |best: Option<(Vec<u8>, f64)>, cur| match best {
+                Some(best) if best.1 >= cur.1 => Some(best),
+                _ => Some(cur),
+            })
+            .unwrap();
+        let plain: Vec<u8> = parsed.text.iter().map(|s| letters[*s]).collect();
+        let score = fitness.score_letters(&plain);
+        let cipher = Self {
+            symbols: parsed.symbols,
+            letters,
+        };
+        if !fitness.is_plausible(&cipher.decrypt(text)) {
+            return Err("no homophonic key decrypts to plausible English");
+        }
+        Ok((cipher, score))
+    }
+}
+
+/// A letter position drawn by English letter frequency
+fn english_letter<R: Rng + ?Sized>(rng: &mut R) -> u8 {
+    let mut x = rng.gen::<f64>() * alpha::ENGLISH_FREQ.iter().sum::<f64>();
+    for (pos, freq) in alpha::ENGLISH_FREQ.iter().enumerate() {
+        if x < *freq {
+            return pos as u8;
+        }
+        x -= freq;
+    }
+    25
+}
+
+/// N-gram score of the letters, less how far their frequencies are from English: the Kullback-Leibler
+/// divergence times the number of letters, in the same log10 units, weighted by `FREQ_WEIGHT`. With many
+/// symbols to play with, n-grams alone turn most of them into e, t and s.
+fn score(plain: &[u8], fitness: &Fitness) -> f64 {
+    let mut counts = [0usize; 26];
+    for pos in plain {
+        counts[*pos as usize] += 1;
+    }
+    let divergence: f64 = counts
+        .iter()
+        .zip(alpha::ENGLISH_FREQ.iter())
+        .filter(|(count, _)| **count > 0)
+        .map(|(count, freq)| {
+            let count = *count as f64;
+            count * (count / plain.len() as f64 / freq).log10()
+        })
+        .sum();
+    fitness.score_letters(plain) - FREQ_WEIGHT * divergence
+}
+
+/// One annealing run from random letters; see `simp_sub::anneal()`. Returns the letters of the symbols.
+fn anneal(
+    parsed: &Symbols,
+    positions: &[Vec<usize>],
+    fitness: &Fitness,
+    schedule: &AnnealSchedule,
+    rng: &mut StdRng,
+) -> (Vec<u8>, f64) {
+    let nsymbols = parsed.symbols.len();
+    let mut letters: Vec<u8> = (0..nsymbols).map(|_| english_letter(rng)).collect();
+    let mut plain: Vec<u8> = parsed.text.iter().map(|s| letters[*s]).collect();
+    let set = |plain: &mut Vec<u8>, symbol: usize, letter: u8| {
+        for pos in positions[symbol].iter() {
+            plain[*pos] = letter;
+        }
+    };
+
+    let mut temp = schedule.temp * plain.len() as f64;
+    let mut score = score(&plain, fitness);
+    let mut best = (letters.clone(), score);
+
+    for _ in 0..schedule.iterations {
+        let s0 = rng.gen_range(0, nsymbols);
+        let old = letters[s0];
+        let swap = if nsymbols > 1 && rng.gen_range(0, 16) < SWAP_MOVES {
+            let s1 = (s0 + rng.gen_range(1, nsymbols)) % nsymbols;
+            letters[s0] = letters[s1];
+            letters[s1] = old;
+            set(&mut plain, s1, old);
+            Some(s1)
+        } else {
+            letters[s0] = english_letter(rng);
+            None
+        };
+        if letters[s0] == old {
+            continue;
+        }
+        set(&mut plain, s0, letters[s0]);
+
+        let new_score = self::score(&plain, fitness);
+        let delta = new_score - score;
+        if delta > 0. || rng.gen::<f64>() < (delta / temp).exp() {
+            score = new_score;
+            if score > best.1 {
+                best = (letters.clone(), score);
+            }
+        } else {
+            if let Some(s1) = swap {
+                letters[s1] = letters[s0];
+                set(&mut plain, s1, letters[s0]);
+            }
+            letters[s0] = old;
+            set(&mut plain, s0, old);
+        }
+        temp *= schedule.cooling;
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ngram::NGram;
+    use crate::test_text::PLAIN;
+
+    #[test]
+    fn test_parse() {
+        let parsed = Symbols::parse("12 7,12\n 105 ,, x7");
+        assert_eq!(parsed.symbols, vec!["12", "7", "105", "x7"]);
+        assert_eq!(parsed.text, vec![0, 1, 0, 2, 3]);
+        assert_eq!(parsed.positions()[0], vec![0, 2]);
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = Homophonic::new(&["1", "2", "30", "4", "55", "6"], "etetah").unwrap();
+        assert_eq!(key.encrypt("The tea!").unwrap(), "2 6 1 4 30 55");
+        assert_eq!(key.decrypt("2 6 1 4 30 55"), "thetea");
+        assert_eq!(key.decrypt("2,6,99"), "th?");
+        assert!(key.encrypt("cat").is_err());
+        assert_eq!(
+            key.homophones(),
+            vec![
+                ('a', vec!["55"]),
+                ('e', vec!["1", "30"]),
+                ('h', vec!["6"]),
+                ('t', vec!["2", "4"])
+            ]
+        );
+
+        assert!(Homophonic::new(&["1", "1"], "ab").is_err());
+        assert!(Homophonic::new(&["1", "2"], "a").is_err());
+        assert!(Homophonic::new(&["1 2"], "a").is_err());
+        assert!(Homophonic::new(&["1"], "!").is_err());
+    }
+
+    #[test]
+    fn test_crack() {
+        let plain = format!(
+            "{} homophonic ciphers take another road to the same end: every common \
+            letter is given several symbols, so that each symbol of the ciphertext turns up about as often as \
+            any other.",
+            PLAIN
+        );
+        // one symbol for every 2.5% or so of English text, and at least one per letter
+        let mut letters = String::new();
+        for (pos, freq) in alpha::ENGLISH_FREQ.iter().enumerate() {
+            let count = ((freq * 40.).round() as usize).max(1);
+            letters.extend(std::iter::repeat_n((b'a' + pos as u8) as char, count));
+        }
+        let symbols: Vec<String> = (0..letters.len()).map(|i| (i + 10).to_string()).collect();
+        let symbols: Vec<&str> = symbols.iter().map(String::as_str).collect();
+        let key = Homophonic::new(&symbols, &letters).unwrap();
+        assert!(symbols.len() > 26);
+        let text = key.encrypt(&plain).unwrap();
+
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let schedule = AnnealSchedule {
+            restarts: 1,
+            ..SCHEDULE
+        };
+        let (cracked, _score) = Homophonic::crack(&text, &fitness, &schedule, 1).unwrap();
+        // symbols that turn up once or twice can't always be told apart
+        let right = cracked
+            .decrypt(&text)
+            .chars()
+            .zip(key.decrypt(&text).chars())
+            .filter(|(a, b)| a == b)
+            .count();
+        assert!(right * 10 >= text.split(' ').count() * 9);
+        assert!(Homophonic::crack(" ,", &fitness, &schedule, 1).is_err());
+    }
+}
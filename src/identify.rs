@@ -0,0 +1,249 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{alpha, caesar, playfair, vigenere};
+
+/// Longest period checked when looking for a periodic (polyalphabetic) key
+const MAX_PERIOD: usize = 20;
+
+/// Fewest letters per column for a period's IoC to be trusted
+const MIN_COLUMN_LEN: usize = 8;
+
+/// Spread of chi-squared against English for text whose letter frequencies are unchanged from English
+const ENGLISH_CHI_SCALE: f64 = 0.5;
+
+/// Least score of monoalphabetic substitution: it is the commonest cipher and the quickest to rule out, so text
+/// that fits no family well, like a short pangram under a Caesar shift, is tried as one first
+const MONO_PRIOR: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CipherFamily {
+    Monoalphabetic,
+    Transposition,
+    Vigenere,
+    Playfair,
+    Polybius,
+}
+
+impl fmt::Display for CipherFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CipherFamily::Monoalphabetic => "monoalphabetic substitution",
+            CipherFamily::Transposition => "transposition",
+            CipherFamily::Vigenere => "vigenere-family polyalphabetic",
+            CipherFamily::Playfair => "playfair",
+            CipherFamily::Polybius => "polybius square / ADFGVX",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Statistical fingerprint of a ciphertext
+#[derive(Debug)]
+pub struct Stats {
+    /// Number of letters
+    pub len: usize,
+    pub ioc: f64,
+    /// Shannon entropy of the letter distribution, in bits
+    pub entropy: f64,
+    /// See `caesar::chi_squared()`; lower means closer to English letter frequencies
+    pub chi_squared: f64,
+    /// Number of distinct letters
+    pub letters_used: usize,
+    /// Number of distinct letters and digits
+    pub symbols_used: usize,
+    /// Same letter twice in a row anywhere
+    pub has_doubles: bool,
+    /// Same letter twice within one of the pairs the letters split into; never happens with Playfair
+    pub has_pair_doubles: bool,
+    pub has_j: bool,
+    /// Period (2 or more) with the highest average column IoC, and that IoC; (1, 0.) for very short texts
+    pub periodic_ioc: (usize, f64),
+}
+
+impl Stats {
+    pub fn new(text: &str) -> Self {
+        let lower = text.to_ascii_lowercase();
+        let letters: Vec<u8> = lower.chars().filter_map(alpha::get_letter_pos).collect();
+        let symbols: HashSet<char> = lower
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+
+        let percent = caesar::get_percentage(&lower);
+        let entropy = -percent
+            .values()
+            .filter(|p| **p > 0.)
+            .map(|p| p * p.log2())
+            .sum::<f64>();
+
+        let max_period = MAX_PERIOD.min(letters.len() / MIN_COLUMN_LEN);
+        let periodic_ioc = (2..=max_period)
+            .map(|len| (len, vigenere::periodic_ioc(&letters, len)))
+            .fold((1, 0.), |best, cur| if cur.1 > best.1 { cur } else { best });
+
+        Self {
+            len: letters.len(),
+            ioc: caesar::index_of_coincidence(&lower),
+            entropy,
+            chi_squared: caesar::chi_squared(&percent),
+            letters_used: letters.iter().collect::<HashSet<_>>().len(),
+            symbols_used: symbols.len(),
+            has_doubles: letters.windows(2).any(|w| w[0] == w[1]),
+            has_pair_doubles: letters.chunks(2).any(|w| w.len() == 2 && w[0] == w[1]),
+            has_j: letters.contains(&(b'j' - b'a')),
+            periodic_ioc,
+        }
+    }
+
+    /// Likely cipher families with confidence between 0 and 1, most likely first
+    pub fn rank(&self) -> Vec<(CipherFamily, f64)> {
+        // 1 when x == mean, falling off like a bell curve
+        let near =
+            |x: f64, mean: f64, spread: f64| (-(x - mean).powi(2) / (2. * spread.powi(2))).exp();
+
+        // a handful of symbols can only be coordinates into a square
+        let polybius = if self.symbols_used >= 2 && self.symbols_used <= 6 {
+            1.
+        } else {
+            0.
+        };
+
+        let english_ioc = near(self.ioc, alpha::ENGLISH_IOC, 0.01);
+        let english_freq = near(self.chi_squared, 0., ENGLISH_CHI_SCALE);
+
+        let mono = (english_ioc * (1. - english_freq)).max(MONO_PRIOR);
+        let transposition = english_ioc * english_freq;
+
+        // polyalphabetic ciphers flatten the frequencies, but splitting by the key length brings them back
+        let periodic_gain = ((self.periodic_ioc.1 - self.ioc)
+            / (alpha::ENGLISH_IOC - alpha::RANDOM_IOC))
+            .clamp(0., 1.);
+        let vigenere = near(self.ioc, 0.045, 0.01) * periodic_gain;
+
+        // any short text with the right letters could be Playfair, and it can only be cracked on long ones
+        let playfair = if self.len.is_multiple_of(2)
+            && !self.has_pair_doubles
+            && !self.has_j
+            && self.letters_used <= 25
+        {
+            let length = (self.len as f64 / playfair::MIN_AUTO_LETTERS as f64).min(1.);
+            near(self.ioc, 0.051, 0.008) * length
+        } else {
+            0.
+        };
+
+        let mut scores = vec![
+            (CipherFamily::Monoalphabetic, mono),
+            (CipherFamily::Transposition, transposition),
+            (CipherFamily::Vigenere, vigenere),
+            (CipherFamily::Playfair, playfair),
+        ];
+        for entry in scores.iter_mut() {
+            entry.1 *= 1. - polybius;
+        }
+        scores.push((CipherFamily::Polybius, polybius));
+
+        let total: f64 = scores.iter().map(|e| e.1).sum();
+        if total > 0. {
+            for entry in scores.iter_mut() {
+                entry.1 /= total;
+            }
+        }
+        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        scores
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "letters: {} ({})",
+            self.len,
+            if self.len.is_multiple_of(2) {
+                "even"
+            } else {
+                "odd"
+            }
+        )?;
+        writeln!(f, "index of coincidence: {:.4}", self.ioc)?;
+        writeln!(f, "entropy: {:.3} bits", self.entropy)?;
+        writeln!(f, "chi-squared against english: {:.3}", self.chi_squared)?;
+        writeln!(
+            f,
+            "distinct letters: {}, distinct symbols: {}",
+            self.letters_used, self.symbols_used
+        )?;
+        writeln!(
+            f,
+            "doubled letters: {}, doubled within pairs: {}, contains j: {}",
+            self.has_doubles, self.has_pair_doubles, self.has_j
+        )?;
+        writeln!(
+            f,
+            "best period: {} (ioc {:.4})",
+            self.periodic_ioc.0, self.periodic_ioc.1
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_text::PLAIN;
+
+    #[test]
+    fn test_rank_transposition() {
+        // a transposition only reorders letters, so reversing the text is one
+        let reversed: String = PLAIN.chars().rev().collect();
+        assert_eq!(
+            Stats::new(&reversed).rank()[0].0,
+            CipherFamily::Transposition
+        );
+    }
+
+    #[test]
+    fn test_rank_monoalphabetic() {
+        let text = crate::caesar::Caesar::encrypt(PLAIN, 7);
+        assert_eq!(Stats::new(&text).rank()[0].0, CipherFamily::Monoalphabetic);
+    }
+
+    #[test]
+    fn test_rank_short_caesar() {
+        // a pangram has flat letter frequencies, like a polyalphabetic cipher
+        let text = crate::caesar::Caesar::encrypt("the quick brown fox jumps over the lazy dog", 1);
+        assert_eq!(Stats::new(&text).rank()[0].0, CipherFamily::Monoalphabetic);
+    }
+
+    #[test]
+    fn test_rank_vigenere() {
+        let text = crate::vigenere::Vigenere::new("lemon")
+            .unwrap()
+            .encrypt(PLAIN);
+        assert_eq!(Stats::new(&text).rank()[0].0, CipherFamily::Vigenere);
+    }
+
+    #[test]
+    fn test_rank_playfair() {
+        let cipher = crate::playfair::Playfair::new("playfair example");
+        let text = cipher.encrypt(&PLAIN.repeat(2));
+        assert_eq!(Stats::new(&text).rank()[0].0, CipherFamily::Playfair);
+
+        // atbash, which only looks like playfair because it is short
+        let text = include_str!("../samples/sample2.txt");
+        assert_eq!(Stats::new(text).rank()[0].0, CipherFamily::Monoalphabetic);
+    }
+
+    #[test]
+    fn test_rank_polybius() {
+        assert_eq!(
+            Stats::new("44 23 15 43 24 32").rank()[0].0,
+            CipherFamily::Polybius
+        );
+        assert_eq!(
+            Stats::new("ADFGVX GVAXDD FAGD").rank()[0].0,
+            CipherFamily::Polybius
+        );
+    }
+}
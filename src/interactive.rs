@@ -0,0 +1,191 @@
+#![allow(dead_code)]
+
+use std::io::{self, prelude::*};
+
+use crate::{alpha, fitness::Fitness, simp_sub, simp_sub::SimpSub};
+
+const HELP: &str = "commands:
+  e=x        plain letter e is encrypted as x; locks e
+  swap a b   swap plain letters a and b
+  unlock e   unlock plain letter e; `unlock` alone unlocks everything
+  undo       undo the last change
+  climb      hill climb again, keeping locked letters fixed
+  key        print the current key
+  show       print the current plaintext
+  help       print this message
+  quit       leave";
+
+/// Result of a successfully run command
+enum Reply {
+    Print(String),
+    Quit,
+}
+
+/// State of an interactive key refinement session for a substitution cipher
+pub struct Session<'a> {
+    text: &'a str,
+    fitness: &'a Fitness<'a>,
+    cipher: SimpSub,
+    /// Plain letters the user has fixed, indexed by position in the alphabet
+    locked: [bool; 26],
+    history: Vec<(SimpSub, [bool; 26])>,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(text: &'a str, fitness: &'a Fitness<'a>, cipher: SimpSub) -> Self {
+        Self {
+            text,
+            fitness,
+            cipher,
+            locked: [false; 26],
+            history: vec![],
+        }
+    }
+
+    pub fn cipher(&self) -> &SimpSub {
+        &self.cipher
+    }
+
+    /// Read commands from `input` until it ends or the user quits, writing responses to `out`
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> io::Result<()> {
+        writeln!(out, "{}", HELP)?;
+        writeln!(out, "{}", self.cipher.decrypt(self.text))?;
+        write!(out, "> ")?;
+        out.flush()?;
+
+        for line in input.lines() {
+            match self.exec(line?.trim()) {
+                Ok(Reply::Print(msg)) => writeln!(out, "{}", msg)?,
+                Ok(Reply::Quit) => break,
+                Err(msg) => writeln!(out, "error: {}", msg)?,
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+
+    /// Run a single command; every command that changes the key replies with the new plaintext
+    fn exec(&mut self, cmd: &str) -> Result<Reply, String> {
+        let parts: Vec<&str> = cmd.split_whitespace().collect();
+        match parts.as_slice() {
+            ["key"] => return Ok(Reply::Print(format!("{:?}", self.cipher))),
+            ["help"] => return Ok(Reply::Print(HELP.to_owned())),
+            ["quit"] | ["exit"] => return Ok(Reply::Quit),
+            [] | ["show"] => {}
+            ["undo"] => {
+                let (cipher, locked) = self.history.pop().ok_or("nothing to undo")?;
+                self.cipher = cipher;
+                self.locked = locked;
+            }
+            ["unlock"] => {
+                self.save();
+                self.locked = [false; 26];
+            }
+            ["unlock", letter] => {
+                let pos = parse_letter(letter)?;
+                self.save();
+                self.locked[pos as usize] = false;
+            }
+            ["swap", a, b] => {
+                let (a, b) = (parse_letter(a)?, parse_letter(b)?);
+                self.check_unlocked(a)?;
+                self.check_unlocked(b)?;
+                self.save();
+                self.cipher.swap_letters_at(letter_at(a), letter_at(b));
+            }
+            ["climb"] => {
+                self.save();
+                let cipher = self.cipher.clone();
+                self.cipher = simp_sub::climb(cipher, self.text, self.fitness, &self.locked).0;
+            }
+            [assign] if assign.len() == 3 && assign.as_bytes()[1] == b'=' => {
+                let plain = parse_letter(&assign[0..1])?;
+                let crypt = parse_letter(&assign[2..3])?;
+
+                // the plain letter currently decrypted from `crypt` gets moved, so it must not be locked either
+                let displaced = parse_letter(&self.cipher.decrypt(&assign[2..3]))?;
+                if displaced != plain {
+                    self.check_unlocked(plain)?;
+                    self.check_unlocked(displaced)?;
+                }
+
+                self.save();
+                self.cipher.change_key(letter_at(plain), letter_at(crypt));
+                self.locked[plain as usize] = true;
+            }
+            _ => return Err(format!("unknown command: {}; type `help` for a list", cmd)),
+        }
+        Ok(Reply::Print(self.cipher.decrypt(self.text)))
+    }
+
+    fn save(&mut self) {
+        self.history.push((self.cipher.clone(), self.locked));
+    }
+
+    fn check_unlocked(&self, pos: u8) -> Result<(), String> {
+        if self.locked[pos as usize] {
+            Err(format!("{} is locked; unlock it first", letter_at(pos)))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn parse_letter(s: &str) -> Result<u8, String> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => alpha::get_letter_pos(c.to_ascii_lowercase())
+            .ok_or_else(|| format!("not a letter: {}", s)),
+        _ => Err(format!("not a letter: {}", s)),
+    }
+}
+
+fn letter_at(pos: u8) -> char {
+    (b'a' + pos) as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ngram::NGram;
+
+    fn session_output(commands: &str) -> (SimpSub, String) {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let cipher = SimpSub::new(alpha::ASCII_LOWER_STR, alpha::ASCII_LOWER_STR).unwrap();
+        let mut session = Session::new("hello world", &fitness, cipher);
+
+        let mut out = vec![];
+        session.run(io::Cursor::new(commands), &mut out).unwrap();
+        (session.cipher().clone(), String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_lock_and_undo() {
+        let (cipher, out) = session_output("h=x\n");
+        assert_eq!(cipher.decrypt("xello world"), "hello world");
+        assert!(out.contains("xello world"));
+
+        let (cipher, _) = session_output("h=x\nundo\n");
+        assert_eq!(cipher.decrypt("hello world"), "hello world");
+    }
+
+    #[test]
+    fn test_locked_letters_stay() {
+        let (cipher, out) = session_output("h=x\nswap h e\n");
+        assert!(out.contains("error: h is locked"));
+        assert_eq!(cipher.encrypt("h"), "x");
+
+        let (cipher, _) = session_output("h=x\nclimb\n");
+        assert_eq!(cipher.encrypt("h"), "x");
+    }
+
+    #[test]
+    fn test_swap_and_quit() {
+        let (cipher, out) = session_output("swap l o\nquit\nswap l o\n");
+        assert!(out.contains("heool wlrod"));
+        assert_eq!(cipher.decrypt("hello world"), "heool wlrod");
+    }
+}
@@ -0,0 +1,234 @@
+#![allow(dead_code)]
+
+//! Keyword recovery: find the dictionary words that could have made a solved substitution key as a keyword
+//! alphabet, see `SimpSub::keyword()`, even when a few letters of the key are wrong or unknown
+
+use std::{cmp::Reverse, fmt};
+
+use rayon::prelude::*;
+
+use crate::{dict::Dict, simp_sub::SimpSub};
+
+/// Shorter words make keyword alphabets too close to the plain alphabet to tell apart
+const MIN_KEYWORD_LEN: usize = 3;
+
+/// Which side of the key the keyword alphabet is on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Plain letters encrypt to the keyword alphabet, like `SimpSub::keyword()`
+    PlainToCipher,
+    /// Cipher letters decrypt to the keyword alphabet
+    CipherToPlain,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::PlainToCipher => write!(f, "plain to cipher"),
+            Direction::CipherToPlain => write!(f, "cipher to plain"),
+        }
+    }
+}
+
+/// A keyword that makes most of a key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeywordMatch {
+    pub keyword: String,
+    pub direction: Direction,
+    /// As in `SimpSub::keyword()`
+    pub offset: u8,
+    pub reversed: bool,
+    /// Letters of the key that the keyword alphabet gets right
+    pub explained: usize,
+    /// Letters of the key that are not uncertain
+    pub known: usize,
+}
+
+impl fmt::Display for KeywordMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.keyword, self.direction)?;
+        if self.offset != 0 {
+            write!(f, ", offset {}", self.offset)?;
+        }
+        if self.reversed {
+            write!(f, ", reversed")?;
+        }
+        write!(f, "): {} of {} letters", self.explained, self.known)
+    }
+}
+
+/// Plain letters whose cipher letter never shows up in `text`, so a solver had nothing to go on for them
+pub fn uncertain_letters(text: &str, key: &SimpSub) -> [bool; 26] {
+    let mut seen = [false; 26];
+    for b in text.bytes().filter(u8::is_ascii_alphabetic) {
+        seen[(b.to_ascii_lowercase() - b'a') as usize] = true;
+    }
+    let (_, to) = key.key_as_str();
+    let mut uncertain = [false; 26];
+    for (plain, crypt) in to.bytes().enumerate() {
+        uncertain[plain] = !seen[(crypt - b'a') as usize];
+    }
+    uncertain
+}
+
+/// The `top` dictionary words whose keyword alphabets agree with the most letters of `key`, best first,
+/// ignoring the plain letters marked in `uncertain`
+///
+/// Every word is tried in both directions, forwards and reversed, at whichever offset lines up the most
+/// letters. Ties go to the shorter word, as "wonderful" and "wonderfuller" make the same alphabet, then to the
+/// first in alphabetical order.
+pub fn find_keywords(
+    key: &SimpSub,
+    uncertain: &[bool; 26],
+    dict: &Dict,
+    top: usize,
+) -> Vec<KeywordMatch> {
+    let (_, to) = key.key_as_str();
+    // (plain, cipher) letter pairs of the key that can be trusted
+    let pairs: Vec<(u8, u8)> = to
+        .bytes()
+        .enumerate()
+        .filter(|(plain, _)| !uncertain[*plain])
+        .map(|(plain, crypt)| (plain as u8, crypt - b'a'))
+        .collect();
+
+    let words: Vec<&str> = dict
+        .iter()
+        .filter(|word| {
+            word.len() >= MIN_KEYWORD_LEN && word.bytes().all(|b| b.is_ascii_lowercase())
+        })
+        .collect();
+    let mut matches: Vec<KeywordMatch> = words
+        .par_iter()
+        .map(|word| best_match(word, &pairs))
+        .collect();
+    matches.sort_by(|a, b| {
+        b.explained
+            .cmp(&a.explained)
+            .then(a.keyword.len().cmp(&b.keyword.len()))
+            .then(a.keyword.cmp(&b.keyword))
+    });
+    matches.truncate(top);
+    matches
+}
+
+/// How `keyword` best lines up with the letter pairs of a key
+fn best_match(keyword: &str, pairs: &[(u8, u8)]) -> KeywordMatch {
+    let (_, alphabet) = SimpSub::keyword(keyword, 0, false).key_as_str();
+    // index of every letter in the keyword alphabet
+    let mut index = [0u8; 26];
+    for (i, b) in alphabet.bytes().enumerate() {
+        index[(b - b'a') as usize] = i as u8;
+    }
+
+    let mut best = KeywordMatch {
+        keyword: keyword.to_owned(),
+        direction: Direction::PlainToCipher,
+        offset: 0,
+        reversed: false,
+        explained: 0,
+        known: pairs.len(),
+    };
+    for &direction in [Direction::PlainToCipher, Direction::CipherToPlain].iter() {
+        for &reversed in [false, true].iter() {
+            // letter `at` is the alphabet letter `of`, so the alphabet starts at `at - index(of)`
+            let mut votes = [0usize; 26];
+            for &(plain, crypt) in pairs {
+                let (at, of) = match direction {
+                    Direction::PlainToCipher => (plain, crypt),
+                    Direction::CipherToPlain => (crypt, plain),
+                };
+                let i = match reversed {
+                    false => index[of as usize],
+                    true => 25 - index[of as usize],
+                };
+                votes[((at + 26 - i) % 26) as usize] += 1;
+            }
+            // ties go to the smaller offset
+            let (offset, &explained) = votes
+                .iter()
+                .enumerate()
+                .max_by_key(|(offset, count)| (**count, Reverse(*offset)))
+                .unwrap();
+            if explained > best.explained {
+                best.direction = direction;
+                best.offset = offset as u8;
+                best.reversed = reversed;
+                best.explained = explained;
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alpha;
+
+    fn dict() -> Dict<'static> {
+        Dict::new(
+            ["kryptos", "krypton", "crypt", "zebras", "zebra", "ab"]
+                .iter()
+                .cloned()
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_find_keywords() {
+        let dict = dict();
+        let key = SimpSub::keyword("kryptos", 3, false);
+        let found = find_keywords(&key, &[false; 26], &dict, 2);
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            found[0],
+            KeywordMatch {
+                keyword: "kryptos".to_owned(),
+                direction: Direction::PlainToCipher,
+                offset: 3,
+                reversed: false,
+                explained: 26,
+                known: 26,
+            }
+        );
+        assert!(found[1].explained < 26);
+        assert_eq!(
+            found[0].to_string(),
+            "kryptos (plain to cipher, offset 3): 26 of 26 letters"
+        );
+
+        // the same alphabet used to decrypt
+        let (_, to) = SimpSub::keyword("zebras", 0, true).key_as_str();
+        let inverse = SimpSub::new(&to, alpha::ASCII_LOWER_STR).unwrap();
+        let found = find_keywords(&inverse, &[false; 26], &dict, 1);
+        assert_eq!(found[0].keyword, "zebras");
+        assert_eq!(found[0].direction, Direction::CipherToPlain);
+        assert!(found[0].reversed);
+        assert_eq!(found[0].explained, 26);
+    }
+
+    #[test]
+    fn test_uncertain_letters() {
+        let dict = dict();
+        let key = SimpSub::keyword("kryptos", 0, false);
+        // "the cat" under the key, so only t, h, e, c and a are certain
+        let text = key.encrypt("the cat");
+        let uncertain = uncertain_letters(&text, &key);
+        assert_eq!(uncertain.iter().filter(|u| !**u).count(), 5);
+
+        // a solver guessed the letters it never saw wrong
+        let mut guessed = key.clone();
+        guessed.swap_letters_at('q', 'z');
+        guessed.swap_letters_at('j', 'x');
+        let mut uncertain = [false; 26];
+        for c in "jqxz".bytes() {
+            uncertain[(c - b'a') as usize] = true;
+        }
+        let found = find_keywords(&guessed, &uncertain, &dict, 1);
+        assert_eq!(found[0].keyword, "kryptos");
+        assert_eq!((found[0].explained, found[0].known), (22, 22));
+        let found = find_keywords(&guessed, &[false; 26], &dict, 1);
+        assert_eq!((found[0].explained, found[0].known), (22, 26));
+    }
+}
@@ -1,96 +1,717 @@
+mod affine;
 mod alpha;
 mod caesar;
+mod cli;
+mod data;
 mod dict;
+mod fitness;
+mod hill;
+mod homophonic;
+mod identify;
+mod interactive;
+mod keyword;
 mod ngram;
+mod pattern;
+mod playfair;
+mod report;
+mod segment;
 mod simp_sub;
+#[cfg(test)]
+mod test_text;
+mod transposition;
+mod vigenere;
 
-use std::{
-    env,
-    fs::File,
-    io::{self, prelude::*},
-    process,
-    time::Instant,
-};
+use std::{fs, io, process, time::Instant};
 
+use structopt::StructOpt;
+
+use affine::Affine;
 #[allow(unused_imports)]
-use caesar::{Caesar, get_percentage, order_percentage};
-// use dict::Dict;
+use caesar::{get_percentage, order_percentage, Caesar};
+use cli::{Cipher, Command, Data, Search};
 use dict::Dict;
+use fitness::{Fitness, Scoring};
+use hill::Hill;
+use homophonic::Homophonic;
+use identify::{CipherFamily, Stats};
+use interactive::Session;
 use ngram::NGram;
+use pattern::PatternDict;
+use playfair::Playfair;
+use report::{Alternative, Format, Key, Solution};
+use simp_sub::{CrackReport, Crib, KeyFamily, SimpSub};
+use transposition::Columnar;
+use vigenere::Vigenere;
 
-fn main() -> io::Result<()> {
-    let args: Vec<_> = env::args().collect();
-    if args.len() < 3 {
-        eprintln!("program analyze|decode [file] [hills]?");
-        process::exit(1);
-    }
-
-    let mut buf = String::new();
+/// Cipher families less likely than this according to `identify` are not tried
+const MIN_CONFIDENCE: f64 = 0.05;
 
-    let mut fin = File::open(&args[2])?;
+/// Fraction of plaintext words that must be in the dictionary to accept the word pattern attack
+const MIN_PATTERN_WORDS: f64 = 0.7;
 
-    fin.read_to_string(&mut buf)?;
-    buf.make_ascii_lowercase();
+fn main() {
+    if let Err(e) = run(Command::from_args()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
 
-    match args[1].as_str() {
-        "analyze" => {
-            let percent = get_percentage(&buf);
+fn run(cmd: Command) -> io::Result<()> {
+    match cmd {
+        Command::Analyze { input, output } => {
+            // only used for analysis; output is always based on the original text so case and layout are kept
+            let lower = input.read()?.to_ascii_lowercase();
+            let percent = get_percentage(&lower);
             let mut order = order_percentage(&percent);
             order.reverse();
 
-            println!("Percent: {:?}", order);
+            report::print_frequencies(output.format, &order);
         }
 
-        "decode" => {
-            let nhills: usize = if args.len() >= 4 {
-                args[3].parse().expect("provide a valid number of hills to climb")
-            } else {
-                500
-            };
+        Command::Decode {
+            input,
+            search,
+            data,
+            cribs,
+            keywords,
+            cipher,
+            output,
+        } => {
+            let text = input.read()?;
+            setup(&search)?;
+            decode(
+                &text,
+                &search,
+                &data,
+                &cribs,
+                keywords,
+                cipher,
+                output.format,
+            )?;
+        }
+
+        Command::Identify { input } => {
+            let stats = Stats::new(&input.read()?);
+            print!("{}", stats);
+            for (family, confidence) in stats.rank() {
+                println!("{:>5.1}% {}", confidence * 100., family);
+            }
+        }
+
+        Command::Interactive { file, search, data } => {
+            let text = fs::read_to_string(&file).map_err(|e| data::with_path(e, &file))?;
+            setup(&search)?;
 
-            // open dict
-            println!("loading dict...");
-            let mut dict_in = File::open("words_alpha.txt")?;
-            let mut dict_buf = String::with_capacity(4264901); // we know this beforehand
-            dict_in.read_to_string(&mut dict_buf)?;
-            let dict = Dict::new(dict_buf.lines().collect());
-            
             println!("loading quadgrams...");
-            let quadgrams = NGram::load_default_quad()?;
-
-            println!("cracking -------------------");
-            let start = Instant::now();
-            let result = Caesar::crack(&buf, &dict);
-            let duration = start.elapsed();
-
-            match result {
-                Some(attempt) => {
-                    println!("Ciphertext: {}", &buf);
-                    println!("Best guess: {}", attempt);
-                    println!("Time taken: {:?}", duration);
-                    process::exit(0);
-                }
-                None => {
-                    println!("Failed to crack with caesar cipher");
+            let quadgrams = data::load_ngrams(data.ngrams.as_deref())?;
+            let words = read_dict(&data, Format::Text)?;
+            let dict = Dict::new(words.lines().collect());
+            let fitness = make_fitness(&text, &quadgrams, &dict, &data);
+
+            let (mut report, seed) = crack_sub_key(&text, &fitness, &dict, &search, Format::Text);
+            let cipher = report.candidates.remove(0).cipher;
+            if let Some(seed) = seed {
+                println!("seed: {}", seed);
+            }
+
+            let stdin = io::stdin();
+            Session::new(&text, &fitness, cipher).run(stdin.lock(), io::stdout())?;
+        }
+    }
+    Ok(())
+}
+
+/// Apply the search options that are global to the process
+fn setup(search: &Search) -> io::Result<()> {
+    search.check().map_err(io::Error::other)?;
+    if let Some(threads) = search.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+    }
+    Ok(())
+}
+
+fn decode(
+    text: &str,
+    search: &Search,
+    data: &Data,
+    cribs: &[Crib],
+    keywords: Option<usize>,
+    cipher: Option<Cipher>,
+    format: Format,
+) -> io::Result<()> {
+    let symbols = homophonic::Symbols::parse(text).text.len();
+    if symbols == 0 {
+        return Err(io::Error::other(
+            "the text is empty: no letters or symbols to crack",
+        ));
+    }
+    let has_letters = text.chars().any(|c| c.is_ascii_alphabetic());
+    if cipher.is_none() && !has_letters && symbols < homophonic::MIN_AUTO_SYMBOLS {
+        return Err(io::Error::other(format!(
+            "the text has no letters, and {} symbols are too few to crack as homophonic; pass --cipher \
+             homophonic to try anyway",
+            symbols
+        )));
+    }
+
+    format.progress("loading quadgrams...");
+    let quadgrams = data::load_ngrams(data.ngrams.as_deref())?;
+    let words = read_dict(data, format)?;
+    let dict = Dict::new(words.lines().collect());
+    let fitness = make_fitness(text, &quadgrams, &dict, data);
+
+    format.progress("cracking -------------------");
+    let ranking = Stats::new(text).rank();
+    let solution = match cipher {
+        Some(Cipher::Hill) if !cribs.is_empty() => {
+            format.progress("solving the key from the cribs...");
+            let solution = try_hill_cribs(text, cribs, &fitness);
+            if solution.is_none() {
+                return Err(io::Error::other(
+                    "no crib gives an invertible hill key; try a longer one",
+                ));
+            }
+            solution
+        }
+        _ if !cribs.is_empty() => {
+            if cipher.is_some_and(|cipher| cipher != Cipher::Substitution) {
+                return Err(io::Error::other(
+                    "--crib only works with substitution and hill",
+                ));
+            }
+            format.progress("placing cribs...");
+            let solution = crack_sub_cribs(text, cribs, &fitness, search)?;
+            if solution.is_none() {
+                return Err(io::Error::other(
+                    "the cribs fit nowhere in the ciphertext, or not together",
+                ));
+            }
+            solution
+        }
+        Some(Cipher::Caesar) => try_caesar(text, &dict, &fitness),
+        Some(Cipher::Affine) => try_affine(text, None, &fitness, search.top),
+        Some(Cipher::Vigenere) => try_vigenere(text, &fitness),
+        Some(Cipher::Transposition) => try_transposition(text, &fitness, search),
+        Some(Cipher::Playfair) => try_playfair(text, &fitness, search, format),
+        Some(Cipher::Hill) => try_hill(text, &fitness, format),
+        Some(Cipher::Homophonic) => try_homophonic(text, &fitness, search, format),
+        None if !has_letters => {
+            format.progress("no letters, trying homophonic substitution...");
+            try_homophonic(text, &fitness, search, format)
+        }
+        Some(Cipher::Substitution) => Some(crack_sub(text, &fitness, &dict, search, format)),
+        None => Some(crack_identified(
+            text, &ranking, &fitness, &dict, search, format,
+        )),
+    };
+
+    match solution {
+        Some(mut solution) => {
+            // the spaces between symbols are not word breaks
+            if !segment::has_word_breaks(text) || matches!(solution.key, Key::Homophonic(_)) {
+                solution.segment(&dict);
+            }
+            if let Some(top) = keywords {
+                format.progress("searching for keywords...");
+                find_keywords(&mut solution.key, text, &dict, top);
+            }
+            solution.print(format, &ranking);
+            Ok(())
+        }
+        None if cipher.is_some() => Err(io::Error::other(
+            "could not crack the text as the given cipher",
+        )),
+        None => Err(io::Error::other(
+            "could not crack the symbols as a homophonic cipher",
+        )),
+    }
+}
+
+/// Fill in the keywords that could have made a substitution key, trusting only the letters seen in text
+fn find_keywords(key: &mut Key, text: &str, dict: &Dict, top: usize) {
+    if let Key::SimpSub {
+        from, to, keywords, ..
+    } = key
+    {
+        let cipher = SimpSub::new(from, to).unwrap();
+        let uncertain = keyword::uncertain_letters(text, &cipher);
+        *keywords = keyword::find_keywords(&cipher, &uncertain, dict, top);
+    }
+}
+
+/// Try the crackers for the likely cipher families in order, falling back to simple substitution
+fn crack_identified(
+    text: &str,
+    ranking: &[(CipherFamily, f64)],
+    fitness: &Fitness,
+    dict: &Dict,
+    search: &Search,
+    format: Format,
+) -> Solution {
+    for (family, confidence) in ranking.iter() {
+        if *confidence < MIN_CONFIDENCE {
+            break;
+        }
+        format.progress(&format!("trying {} ({:.1}%)...", family, confidence * 100.));
+
+        let solution = match family {
+            CipherFamily::Monoalphabetic => {
+                let solution = try_caesar(text, dict, fitness).or_else(|| {
+                    format.progress("Failed to crack with caesar cipher, trying affine...");
+                    try_affine(text, Some(dict), fitness, search.top)
+                });
+                if solution.is_none() {
+                    // substitution is the last resort below, once the other likely families have failed too
+                    format.progress("Failed to crack with affine cipher");
+                    continue;
                 }
+                solution
+            }
+            CipherFamily::Vigenere => try_vigenere(text, fitness),
+            CipherFamily::Transposition => try_transposition(text, fitness, search),
+            CipherFamily::Playfair => try_playfair(text, fitness, search, format),
+            family => {
+                format.progress(&format!("no cracker for {} yet", family));
+                continue;
             }
+        };
+
+        match solution {
+            Some(solution) => return solution,
+            None => format.progress(&format!("Failed to crack as {}", family)),
+        }
+    }
 
-            println!("climbing hills...");
+    crack_sub(text, fitness, dict, search, format)
+}
 
-            let start = Instant::now();
-            let result = simp_sub::rand_crack(&buf, &quadgrams, nhills);
-            let duration = start.elapsed();
+/// Read the word list; `Dict` borrows the words from the returned string
+fn read_dict(data: &Data, format: Format) -> io::Result<String> {
+    format.progress("loading dict...");
+    data::read_words(data.dict.as_deref())
+}
 
-            println!(
-                "Best result: {}, quad_gram_score: {}",
-                result.0.decrypt(&buf),
-                result.1
-            );
-            println!("time taken: {:?}", duration);
+/// Quadgram fitness for cracking `text`, with the dictionary bonus if `--word-weight` is given. The words of a
+/// text without word breaks mean nothing, so it is always scored as a stream of letters.
+fn make_fitness<'a>(
+    text: &str,
+    quadgrams: &'a NGram,
+    dict: &'a Dict<'a>,
+    data: &Data,
+) -> Fitness<'a> {
+    if !segment::has_word_breaks(text) {
+        return Fitness::new(quadgrams);
+    }
+    let fitness = match data.word_weight {
+        Some(weight) => Fitness::with_dict(quadgrams, dict, weight),
+        None => Fitness::new(quadgrams),
+    };
+    fitness.with_scoring(data.scoring.unwrap_or(Scoring::Letters))
+}
 
+/// Crack text as a Caesar cipher that decrypts to dictionary words
+fn try_caesar(text: &str, dict: &Dict, fitness: &Fitness) -> Option<Solution> {
+    let start = Instant::now();
+    let (shift, plaintext) = Caesar::crack(text, dict)?;
+    Some(Solution {
+        key: Key::Caesar(shift),
+        score: fitness.score_text(&plaintext),
+        plaintext,
+        hills: None,
+        seed: None,
+        elapsed: start.elapsed(),
+        alternatives: vec![],
+    })
+}
+
+/// Crack text as an affine cipher, keeping the `top` best keys, and report keys with a = 1 as Caesar shifts.
+/// With a dict, the best key only counts if it decrypts to dictionary words, or to plausible English for text
+/// without word breaks.
+fn try_affine(text: &str, dict: Option<&Dict>, fitness: &Fitness, top: usize) -> Option<Solution> {
+    let start = Instant::now();
+    let mut ranked = Affine::crack(text, fitness, top.max(1))
+        .into_iter()
+        .map(|(cipher, score)| Alternative {
+            key: match (cipher.a(), cipher.b()) {
+                (1, b) => Key::Caesar(b as i32),
+                (25, 25) => {
+                    let (from, to) = SimpSub::atbash().key_as_str();
+                    Key::SimpSub {
+                        from,
+                        to,
+                        family: Some(KeyFamily::Atbash),
+                        keywords: vec![],
+                    }
+                }
+                (a, b) => Key::Affine { a, b },
+            },
+            plaintext: cipher.decrypt(text),
+            score,
+        });
+
+    let best = ranked.next()?;
+    if let Some(dict) = dict {
+        let readable = if segment::has_word_breaks(text) {
+            dict.str_contains_every_word(&best.plaintext.to_ascii_lowercase())
+        } else {
+            fitness.is_plausible(&best.plaintext)
+        };
+        if !readable {
+            return None;
         }
+    }
+    Some(Solution {
+        key: best.key,
+        plaintext: best.plaintext,
+        score: best.score,
+        hills: None,
+        seed: None,
+        elapsed: start.elapsed(),
+        alternatives: ranked.collect(),
+    })
+}
+
+fn try_vigenere(text: &str, fitness: &Fitness) -> Option<Solution> {
+    let start = Instant::now();
+    let (cipher, score) = Vigenere::crack(&text.to_ascii_lowercase(), fitness)?;
+    Some(Solution {
+        plaintext: cipher.decrypt(text),
+        key: match cipher.shift() {
+            Some(shift) => Key::Caesar(shift as i32),
+            None => Key::Vigenere(cipher.key()),
+        },
+        score,
+        hills: None,
+        seed: None,
+        elapsed: start.elapsed(),
+        alternatives: vec![],
+    })
+}
 
-        cmd => println!("unknown command: {}", cmd),
+fn try_transposition(text: &str, fitness: &Fitness, search: &Search) -> Option<Solution> {
+    let start = Instant::now();
+    let seed = search.seed.unwrap_or_else(rand::random);
+    let (cipher, score) = Columnar::crack(text, fitness, seed)?;
+    Some(Solution {
+        plaintext: cipher.decrypt(text),
+        key: Key::Columnar(cipher.order().to_vec()),
+        score,
+        hills: None,
+        seed: Some(seed),
+        elapsed: start.elapsed(),
+        alternatives: vec![],
+    })
+}
+
+/// Crack text as a Playfair cipher, unless it can't be one
+fn try_playfair(
+    text: &str,
+    fitness: &Fitness,
+    search: &Search,
+    format: Format,
+) -> Option<Solution> {
+    let start = Instant::now();
+    let seed = search.seed.unwrap_or_else(rand::random);
+    format.progress("annealing...");
+    let (cipher, score) = match Playfair::crack(text, fitness, &playfair::SCHEDULE, seed) {
+        Ok(cracked) => cracked,
+        Err(e) => {
+            format.progress(e);
+            return None;
+        }
+    };
+    Some(Solution {
+        plaintext: Playfair::remove_fillers(&cipher.decrypt(text)),
+        key: Key::Playfair(cipher.square_str()),
+        score,
+        hills: None,
+        seed: Some(seed),
+        elapsed: start.elapsed(),
+        alternatives: vec![],
+    })
+}
+
+/// Crack text as a 2x2 Hill cipher, unless it can't be one
+fn try_hill(text: &str, fitness: &Fitness, format: Format) -> Option<Solution> {
+    let start = Instant::now();
+    match Hill::crack(text, fitness) {
+        Ok((cipher, score)) => Some(hill_solution(text, cipher, score, start)),
+        Err(e) => {
+            format.progress(e);
+            None
+        }
+    }
+}
+
+/// Solve a 2x2 or 3x3 Hill key from each crib on its own, and keep the key that decrypts best
+fn try_hill_cribs(text: &str, cribs: &[Crib], fitness: &Fitness) -> Option<Solution> {
+    let start = Instant::now();
+    let (cipher, score) = [2, 3]
+        .iter()
+        .flat_map(|size| {
+            cribs
+                .iter()
+                .filter_map(move |crib| Hill::from_crib(text, crib, *size, fitness).ok())
+        })
+        .fold(None, |best: Option<(Hill, f64)>, cur| match best {
+            Some(best) if best.1 >= cur.1 => Some(best),
+            _ => Some(cur),
+        })?;
+    Some(hill_solution(text, cipher, score, start))
+}
+
+fn hill_solution(text: &str, cipher: Hill, score: f64, start: Instant) -> Solution {
+    Solution {
+        plaintext: cipher.decrypt(text),
+        key: Key::Hill(cipher.rows()),
+        score,
+        hills: None,
+        seed: None,
+        elapsed: start.elapsed(),
+        alternatives: vec![],
+    }
+}
+
+/// Crack text of symbols as a homophonic substitution cipher
+fn try_homophonic(
+    text: &str,
+    fitness: &Fitness,
+    search: &Search,
+    format: Format,
+) -> Option<Solution> {
+    let start = Instant::now();
+    let seed = search.seed.unwrap_or_else(rand::random);
+    format.progress("annealing...");
+    let (cipher, score) = match Homophonic::crack(text, fitness, &homophonic::SCHEDULE, seed) {
+        Ok(cracked) => cracked,
+        Err(e) => {
+            format.progress(e);
+            return None;
+        }
+    };
+    let homophones = cipher
+        .homophones()
+        .into_iter()
+        .map(|(letter, symbols)| (letter, symbols.into_iter().map(str::to_owned).collect()))
+        .collect();
+    Some(Solution {
+        plaintext: cipher.decrypt(text),
+        key: Key::Homophonic(homophones),
+        score,
+        hills: None,
+        seed: Some(seed),
+        elapsed: start.elapsed(),
+        alternatives: vec![],
+    })
+}
+
+/// Crack text as a simple substitution cipher
+fn crack_sub(
+    text: &str,
+    fitness: &Fitness,
+    dict: &Dict,
+    search: &Search,
+    format: Format,
+) -> Solution {
+    let start = Instant::now();
+    let (report, seed) = crack_sub_key(text, fitness, dict, search, format);
+    sub_solution(report, seed, start)
+}
+
+/// Report the best key of a substitution cracker, with the rest as alternatives. Only a random search, with a
+/// seed, reports its hills; the hills of a deterministic solve say nothing about how sure it is.
+fn sub_solution(report: CrackReport, seed: Option<u64>, start: Instant) -> Solution {
+    let hills = seed.map(|_| (report.hills, report.best_hits));
+    let mut candidates = report.candidates.into_iter().map(|candidate| {
+        let (from, to) = candidate.cipher.key_as_str();
+        Alternative {
+            key: Key::SimpSub {
+                from,
+                to,
+                family: candidate.cipher.family(),
+                keywords: vec![],
+            },
+            plaintext: candidate.plaintext,
+            score: candidate.score,
+        }
+    });
+    let best = candidates.next().unwrap();
+    Solution {
+        key: best.key,
+        plaintext: best.plaintext,
+        score: best.score,
+        hills,
+        seed,
+        elapsed: start.elapsed(),
+        alternatives: candidates.collect(),
+    }
+}
+
+/// Crack text as a simple substitution cipher containing the known plaintext `cribs`; None if the cribs fit
+/// nowhere
+fn crack_sub_cribs(
+    text: &str,
+    cribs: &[Crib],
+    fitness: &Fitness,
+    search: &Search,
+) -> io::Result<Option<Solution>> {
+    let start = Instant::now();
+    let hills = search.crib_hills().map_err(io::Error::other)?;
+    let seed = search.seed.unwrap_or_else(rand::random);
+    let report = simp_sub::crib_crack(text, cribs, fitness, hills, seed, search.top);
+    Ok(report.map(|report| sub_solution(report, Some(seed), start)))
+}
+
+/// Crack text as a simple substitution cipher, first by matching word patterns unless `search` asks for a
+/// particular search, then with hill climbing or annealing depending on `search`. Keeps the `search.top` best
+/// keys, and returns the seed of the random search, if it came to that.
+fn crack_sub_key(
+    text: &str,
+    fitness: &Fitness,
+    dict: &Dict,
+    search: &Search,
+    format: Format,
+) -> (CrackReport, Option<u64>) {
+    if segment::has_word_breaks(text) && !search.is_explicit() {
+        if let Some(report) = crack_sub_patterns(text, fitness, dict, search.top, format) {
+            return (report, None);
+        }
+    }
+
+    let seed = search.seed.unwrap_or_else(rand::random);
+    let report = match search.stop_rule() {
+        Some(stop) => {
+            format.progress("climbing hills...");
+            simp_sub::rand_crack(text, fitness, &stop, seed, search.top)
+        }
+        None => {
+            // on short texts quadgrams alone can prefer gibberish, so keep the letters the words agree on
+            let known = if segment::has_word_breaks(text) {
+                format.progress("matching word patterns...");
+                simp_sub::known_letters(text, &PatternDict::new(dict))
+            } else {
+                None
+            };
+            format.progress("annealing...");
+            simp_sub::anneal_crack(
+                text,
+                fitness,
+                &search.schedule(),
+                known.as_ref(),
+                seed,
+                search.top,
+            )
+        }
+    };
+    (report, Some(seed))
+}
+
+/// Crack text with the word pattern attack; None unless enough of the plaintext words are in the dictionary
+fn crack_sub_patterns(
+    text: &str,
+    fitness: &Fitness,
+    dict: &Dict,
+    top: usize,
+    format: Format,
+) -> Option<CrackReport> {
+    format.progress("matching word patterns...");
+    let patterns = PatternDict::new(dict);
+    let report = simp_sub::crack(text, &patterns, fitness, top)?;
+
+    let plaintext = report.candidates[0].plaintext.to_lowercase();
+    let words: Vec<String> = plaintext.split_whitespace().map(alpha::rm_punct).collect();
+    if dict.count_words(&words) as f64 >= MIN_PATTERN_WORDS * words.len() as f64 {
+        Some(report)
+    } else {
+        format.progress("Failed to match word patterns");
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crack_identified_pattern_attack() {
+        let quadgrams = NGram::load_default_quad().unwrap();
+        let words = data::read_words(None).unwrap();
+        let dict = Dict::new(words.lines().collect());
+        let search = Search::from_iter(&["decode"]);
+
+        // so short that quadgrams alone prefer "peware the fapperwock"
+        let text = include_str!("../samples/sample5.txt");
+        let fitness = make_fitness(text, &quadgrams, &dict, &Data::from_iter(&["decode"]));
+        let ranking = Stats::new(text).rank();
+        let solution = crack_identified(text, &ranking, &fitness, &dict, &search, Format::Json);
+        assert!(solution.seed.is_none());
+        assert!(solution
+            .plaintext
+            .starts_with("twas brillig, and the slithy toves"));
+        assert!(solution
+            .plaintext
+            .contains("beware the jabberwock, my son!"));
+
+        // short, with common words that fit many others
+        let text = include_str!("../samples/sample6.txt");
+        let fitness = make_fitness(text, &quadgrams, &dict, &Data::from_iter(&["decode"]));
+        let ranking = Stats::new(text).rank();
+        let solution = crack_identified(text, &ranking, &fitness, &dict, &search, Format::Json);
+        assert!(solution.seed.is_none());
+        assert!(solution
+            .plaintext
+            .starts_with("LIFE IS ALWAYS A RICH AND STEADY\nTIME WHEN YOU ARE WAITING FOR"));
+    }
+
+    #[test]
+    fn test_crack_identified_short_text() {
+        let quadgrams = NGram::load_default_quad().unwrap();
+        let words = data::read_words(None).unwrap();
+        let dict = Dict::new(words.lines().collect());
+        let fitness = Fitness::new(&quadgrams);
+        let search = Search::from_iter(&["decode", "--seed", "1"]);
+
+        // atbash, short enough that it could be playfair
+        let text = include_str!("../samples/sample2.txt");
+        let ranking = Stats::new(text).rank();
+        assert_eq!(ranking[0].0, CipherFamily::Monoalphabetic);
+        let solution = crack_identified(text, &ranking, &fitness, &dict, &search, Format::Json);
+        assert!(matches!(
+            solution.key,
+            Key::SimpSub {
+                family: Some(KeyFamily::Atbash),
+                ..
+            }
+        ));
+        assert_eq!(
+            solution.key.to_string().lines().last(),
+            Some("key family: atbash")
+        );
+        assert!(solution
+            .plaintext
+            .starts_with("TO DECODE THIS, YOU SOLVED AN ATBASH CIPHER."));
+    }
+
+    #[test]
+    fn test_decode_too_few_symbols() {
+        let search = Search::from_iter(&["decode"]);
+        let data = Data::from_iter(&["decode"]);
+        let decode = |text| decode(text, &search, &data, &[], None, None, Format::Json);
+        for empty in &["", " \n", ",, ,"] {
+            assert!(decode(empty)
+                .unwrap_err()
+                .to_string()
+                .starts_with("the text is empty"));
+        }
+        // identified as polybius, which has no cracker
+        for short in &["!!!", "44 23 15 43 24 32"] {
+            assert!(decode(short)
+                .unwrap_err()
+                .to_string()
+                .contains("too few to crack as homophonic"));
+        }
     }
-    Ok(())
 }
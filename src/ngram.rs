@@ -1,14 +1,25 @@
+#![allow(dead_code)]
+
 use std::{
     collections::HashMap,
     fs::File,
     io::{self, prelude::*},
+    path::Path,
 };
 
 use io::BufReader;
 
+use crate::data;
+
+/// Largest n for which a packed table is built; 26^4 f64s take about 3.6MB
+const MAX_PACKED_N: usize = 4;
+
 pub struct NGram {
     n: usize,
     prob: HashMap<String, f64>,
+    /// Log probability of every possible ngram, indexed by the ngram read as a base 26 number.
+    /// Empty if n is too large for a dense table.
+    packed: Vec<f64>,
     floor: f64,
 }
 
@@ -22,37 +33,126 @@ impl NGram {
             }
         })
     }
+
+    /// Same as `score_chars()`, but takes letter positions (0 for 'a') and does not allocate.
+    /// Assumes every letter is less than 26.
+    pub fn score_letters(&self, letters: &[u8]) -> f64 {
+        if self.packed.is_empty() {
+            let chars: Vec<char> = letters.iter().map(|l| (b'a' + l) as char).collect();
+            return self.score_chars(&chars);
+        }
+        if letters.len() < self.n {
+            return 0.;
+        }
+
+        let modulo = self.packed.len();
+        let mut index = pack(&letters[..self.n - 1]);
+        letters[self.n - 1..].iter().fold(0., |accu, letter| {
+            index = (index * 26 + *letter as usize) % modulo;
+            accu + self.packed[index]
+        })
+    }
+}
+
+/// Read letter positions as a base 26 number
+fn pack(letters: &[u8]) -> usize {
+    letters
+        .iter()
+        .fold(0, |index, letter| index * 26 + *letter as usize)
 }
 
 impl NGram {
+    /// The quadgram table embedded in the binary; see `data`
     pub fn load_default_quad() -> io::Result<Self> {
-        let fin = BufReader::new(File::open("quadgrams.txt")?);
+        data::embedded_quadgrams()
+    }
 
-        let mut prob = HashMap::with_capacity(389373);
+    /// Load an ngram frequency table, one `ngram count` pair per line
+    ///
+    /// If `n` is None, it is inferred from the first ngram in the file
+    pub fn from_file<P: AsRef<Path>>(path: P, n: Option<usize>) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?), n)
+    }
 
-        let mut words: Vec<String> = vec![];
-        let mut freqs: Vec<usize> = vec![];
+    /// Same as `from_file()`, but reads the table from any buffered reader
+    pub fn from_reader<R: BufRead>(reader: R, n: Option<usize>) -> io::Result<Self> {
+        let mut n = n;
+        let mut counts: Vec<(String, u64)> = vec![];
 
-        for line in fin.lines() {
+        for (i, line) in reader.lines().enumerate() {
             let line = line?;
+            let line_num = i + 1;
+
             let mut parts = line.split_whitespace();
-            words.push(parts.next().unwrap().to_owned());
-            freqs.push(parts.next().unwrap().trim_start().parse().unwrap());
+            let (gram, count) = match (parts.next(), parts.next(), parts.next()) {
+                (None, _, _) => continue,
+                (Some(gram), Some(count), None) => (gram.to_ascii_lowercase(), count),
+                _ => return Err(invalid_line(line_num, "expected an ngram and a count")),
+            };
+
+            if !gram.chars().all(|c| c.is_ascii_lowercase()) {
+                return Err(invalid_line(line_num, "ngram must only contain letters"));
+            }
+            let expected = *n.get_or_insert(gram.len());
+            if gram.len() != expected {
+                return Err(invalid_line(
+                    line_num,
+                    &format!("expected an ngram of length {}, found {:?}", expected, gram),
+                ));
+            }
+
+            let count: u64 = count
+                .parse()
+                .map_err(|_| invalid_line(line_num, "count must be a non-negative integer"))?;
+            counts.push((gram, count));
         }
 
-        let total_quadgrams: f64 = freqs.iter().sum::<usize>() as f64;
+        let n = match n {
+            Some(n) if n > 0 && !counts.is_empty() => n,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "ngram table is empty",
+                ))
+            }
+        };
+
+        let total: f64 = counts.iter().map(|e| e.1).sum::<u64>() as f64;
+        let prob: HashMap<String, f64> = counts
+            .into_iter()
+            .map(|(gram, count)| (gram, (count as f64 / total).log10()))
+            .collect();
+        let floor = (0.01 / total).log10();
 
-        for (word, freq) in words.into_iter().zip(freqs.iter()) {
-            prob.insert(word, (*freq as f64 / total_quadgrams).log10());
+        let mut packed = vec![];
+        if n <= MAX_PACKED_N {
+            packed = vec![floor; 26usize.pow(n as u32)];
+            for (gram, p) in prob.iter() {
+                packed[pack(&gram.bytes().map(|b| b - b'a').collect::<Vec<u8>>())] = *p;
+            }
         }
-        let floor = (0.01 / total_quadgrams).log10();
 
-        // dbg!(&prob);
+        Ok(Self {
+            n,
+            prob,
+            packed,
+            floor,
+        })
+    }
 
-        Ok(Self { n: 4, prob, floor })
+    /// Length of each ngram in this table
+    pub fn n(&self) -> usize {
+        self.n
     }
 }
 
+fn invalid_line(line_num: usize, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid ngram table at line {}: {}", line_num, msg),
+    )
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -75,4 +175,40 @@ mod tests {
         assert!(score_eng > score_nonsense);
         dbg!(score_eng, score_nonsense);
     }
+
+    #[test]
+    fn test_packed_matches_hashmap() {
+        let quads = NGram::load_default_quad().unwrap();
+
+        for text in &[
+            "attacktheeastwallofthecastleatdawn",
+            "fyyfhpymjjfxybfqqtkymjhfxyqjfyifbs",
+            "qzqzqzjjjjxxxx",
+            "the",
+            "",
+        ] {
+            let chars: Vec<char> = text.chars().collect();
+            let letters: Vec<u8> = chars.iter().map(|c| *c as u8 - b'a').collect();
+            assert_eq!(quads.score_chars(&chars), quads.score_letters(&letters));
+        }
+    }
+
+    #[test]
+    fn test_from_reader_infers_n() {
+        let bigrams = NGram::from_reader(io::Cursor::new("TH 30\nHE 20\n\nIN 10\n"), None).unwrap();
+        assert_eq!(bigrams.n(), 2);
+
+        let score = bigrams.score_chars(&['t', 'h', 'e']);
+        assert!((score - ((0.5f64).log10() + (1. / 3.0f64).log10())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_from_reader_rejects_malformed() {
+        assert!(NGram::from_reader(io::Cursor::new("the 10\nth 5\n"), None).is_err());
+        assert!(NGram::from_reader(io::Cursor::new("the 10\n"), Some(4)).is_err());
+        assert!(NGram::from_reader(io::Cursor::new("the ten\n"), None).is_err());
+        assert!(NGram::from_reader(io::Cursor::new("t3e 10\n"), None).is_err());
+        assert!(NGram::from_reader(io::Cursor::new("the\n"), None).is_err());
+        assert!(NGram::from_reader(io::Cursor::new(""), None).is_err());
+    }
 }
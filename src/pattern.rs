@@ -0,0 +1,298 @@
+#![allow(dead_code)]
+
+//! Word pattern attack on substitution ciphers: a cipher word can only decrypt to dictionary words with the same
+//! pattern of repeated letters, e.g. "qiccznjbla" to "jabberwock", so with word boundaries kept most of the key
+//! follows from fitting the words together
+
+use std::collections::HashMap;
+
+use crate::{alpha, dict::Dict};
+
+/// Cipher letter to plain letter, as positions in the alphabet, where known
+pub type PartialKey = [Option<u8>; 26];
+
+/// Marks a letter without a mapping in `Assignment`
+const UNKNOWN: u8 = 26;
+
+/// The only one-letter words worth trying; the word list has every letter as a word
+const ONE_LETTER_WORDS: [&str; 2] = ["a", "i"];
+
+/// Search steps tried before giving up on finding more keys
+const MAX_NODES: usize = 5000;
+
+/// Fraction of the distinct cipher words allowed to have no dictionary match, for names and made up words
+const MISS_RATIO: f64 = 0.25;
+
+/// Letter pattern of a word: every letter replaced by the order in which it first appears, so "that" and
+/// "high" are both [0, 1, 2, 0]. Assumes `word` is lower ascii letters.
+pub fn pattern(word: &[u8]) -> Vec<u8> {
+    let mut seen = [UNKNOWN; 26];
+    let mut next = 0;
+    word.iter()
+        .map(|b| {
+            let pos = (b - b'a') as usize;
+            if seen[pos] == UNKNOWN {
+                seen[pos] = next;
+                next += 1;
+            }
+            seen[pos]
+        })
+        .collect()
+}
+
+/// Dictionary words grouped by letter pattern
+pub struct PatternDict<'a> {
+    /// Most common looking words first, going by their letter frequencies
+    index: HashMap<Vec<u8>, Vec<&'a str>>,
+}
+
+impl<'a> PatternDict<'a> {
+    pub fn new(dict: &Dict<'a>) -> Self {
+        let mut index: HashMap<Vec<u8>, Vec<&'a str>> = HashMap::new();
+        for word in dict.iter() {
+            if word.len() > 1 && word.bytes().all(|b| b.is_ascii_lowercase()) {
+                index
+                    .entry(pattern(word.as_bytes()))
+                    .or_default()
+                    .push(word);
+            }
+        }
+        for words in index.values_mut() {
+            // the word set has no order, so break ties by the word to keep the search repeatable
+            words.sort_by(|a, b| {
+                commonness(b)
+                    .partial_cmp(&commonness(a))
+                    .unwrap()
+                    .then(a.cmp(b))
+            });
+        }
+        Self { index }
+    }
+
+    /// Dictionary words with the same pattern as `word`; Assumes `word` is lower ascii letters
+    pub fn matches(&self, word: &str) -> &[&'a str] {
+        if word.len() == 1 {
+            return &ONE_LETTER_WORDS;
+        }
+        self.index
+            .get(&pattern(word.as_bytes()))
+            .map_or(&[], |words| words.as_slice())
+    }
+}
+
+/// Log likelihood of the letters of a word in English text; only meaningful between words of the same length
+fn commonness(word: &str) -> f64 {
+    word.bytes()
+        .map(|b| alpha::ENGLISH_FREQ[(b - b'a') as usize].ln())
+        .sum()
+}
+
+/// Find up to `max_keys` partial keys under which the words of `text` decrypt to dictionary words, best first
+///
+/// Words are fitted one at a time, always picking the word with the fewest dictionary words still consistent
+/// with the key so far, and backtracking when one has none. The search first allows no word to be left
+/// unmatched, then one more at a time up to a quarter of the distinct words, so that a few lucky matches early
+/// on can't crowd out a key that fits every word. Keys that match more words come first.
+pub fn solve(text: &str, patterns: &PatternDict, max_keys: usize) -> Vec<PartialKey> {
+    let lower = text.to_lowercase();
+    let mut words: Vec<String> = lower
+        .split_whitespace()
+        .map(alpha::rm_punct)
+        .filter(|word| !word.is_empty() && word.bytes().all(|b| b.is_ascii_lowercase()))
+        .collect();
+    // longest first, so ties in the search go to the word that fixes the most letters
+    words.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    words.dedup();
+
+    let words: Vec<(&[u8], &[&str])> = words
+        .iter()
+        .map(|word| (word.as_bytes(), patterns.matches(word)))
+        .filter(|(_, matches)| !matches.is_empty())
+        .collect();
+    if words.is_empty() {
+        return vec![];
+    }
+
+    let remaining: Vec<usize> = (0..words.len()).collect();
+    let mut keys = vec![];
+    for max_misses in 0..=(words.len() as f64 * MISS_RATIO) as usize {
+        let mut search = Search {
+            words: &words,
+            max_misses,
+            max_keys,
+            nodes: 0,
+            keys: vec![],
+        };
+        search.run(&mut Assignment::default(), &remaining, 0);
+        keys = search.keys;
+        if !keys.is_empty() {
+            break;
+        }
+    }
+    keys.sort_by_key(|(_, misses)| *misses);
+    keys.into_iter().map(|(key, _)| key).collect()
+}
+
+/// Mapping built up during the search, both ways, so one-to-one conflicts are quick to find
+struct Assignment {
+    /// Plain letter of each cipher letter
+    plain: [u8; 26],
+    /// Cipher letter of each plain letter
+    crypt: [u8; 26],
+}
+
+impl Default for Assignment {
+    fn default() -> Self {
+        Self {
+            plain: [UNKNOWN; 26],
+            crypt: [UNKNOWN; 26],
+        }
+    }
+}
+
+impl Assignment {
+    /// Whether `crypt` can decrypt to `plain`; Assumes they have the same pattern
+    fn fits(&self, crypt: &[u8], plain: &str) -> bool {
+        crypt.iter().zip(plain.bytes()).all(|(c, p)| {
+            let (c, p) = ((c - b'a') as usize, p - b'a');
+            match self.plain[c] {
+                UNKNOWN => self.crypt[p as usize] == UNKNOWN,
+                known => known == p,
+            }
+        })
+    }
+
+    /// Map `crypt` to `plain` and return the cipher letters that were newly mapped
+    fn assign(&mut self, crypt: &[u8], plain: &str) -> Vec<usize> {
+        let mut new = vec![];
+        for (c, p) in crypt.iter().zip(plain.bytes()) {
+            let (c, p) = ((c - b'a') as usize, p - b'a');
+            if self.plain[c] == UNKNOWN {
+                self.plain[c] = p;
+                self.crypt[p as usize] = c as u8;
+                new.push(c);
+            }
+        }
+        new
+    }
+
+    fn unassign(&mut self, crypts: &[usize]) {
+        for &c in crypts {
+            self.crypt[self.plain[c] as usize] = UNKNOWN;
+            self.plain[c] = UNKNOWN;
+        }
+    }
+
+    fn to_partial_key(&self) -> PartialKey {
+        let mut key = [None; 26];
+        for (c, p) in self.plain.iter().enumerate() {
+            if *p != UNKNOWN {
+                key[c] = Some(*p);
+            }
+        }
+        key
+    }
+}
+
+/// Depth first search state of `solve()`
+struct Search<'w, 'a> {
+    /// Distinct cipher words with their dictionary matches
+    words: &'w [(&'w [u8], &'w [&'a str])],
+    max_misses: usize,
+    max_keys: usize,
+    nodes: usize,
+    /// Keys found, with the number of words they leave unmatched
+    keys: Vec<(PartialKey, usize)>,
+}
+
+impl Search<'_, '_> {
+    /// Fit the words at indices `remaining`, with `misses` words skipped so far
+    fn run(&mut self, assignment: &mut Assignment, remaining: &[usize], misses: usize) {
+        if self.nodes >= MAX_NODES || self.keys.len() >= self.max_keys {
+            return;
+        }
+        self.nodes += 1;
+        if remaining.is_empty() {
+            self.keys.push((assignment.to_partial_key(), misses));
+            return;
+        }
+
+        // most constrained word first; counting stops once a word can't beat the best so far
+        let mut best = (0, usize::MAX);
+        for (i, &w) in remaining.iter().enumerate() {
+            let (crypt, matches) = self.words[w];
+            let count = matches
+                .iter()
+                .filter(|plain| assignment.fits(crypt, plain))
+                .take(best.1)
+                .count();
+            if count < best.1 {
+                best = (i, count);
+                if count == 0 {
+                    break;
+                }
+            }
+        }
+
+        let (i, count) = best;
+        let rest: Vec<usize> = remaining
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, w)| *w)
+            .collect();
+        if count == 0 {
+            if misses < self.max_misses {
+                self.run(assignment, &rest, misses + 1);
+            }
+            return;
+        }
+
+        let (crypt, matches) = self.words[remaining[i]];
+        for plain in matches.iter() {
+            if !assignment.fits(crypt, plain) {
+                continue;
+            }
+            let new = assignment.assign(crypt, plain);
+            self.run(assignment, &rest, misses);
+            assignment.unassign(&new);
+            if self.nodes >= MAX_NODES || self.keys.len() >= self.max_keys {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_pattern() {
+        assert_eq!(pattern(b"that"), vec![0, 1, 2, 0]);
+        assert_eq!(pattern(b"high"), pattern(b"that"));
+        assert_eq!(pattern(b"qiccznjbla"), pattern(b"jabberwock"));
+        assert_ne!(pattern(b"the"), pattern(b"see"));
+    }
+
+    #[test]
+    fn test_solve() {
+        let words: HashSet<&str> = ["the", "cat", "dog", "sees", "tree", "see"]
+            .iter()
+            .cloned()
+            .collect();
+        let dict = Dict::new(words);
+        let patterns = PatternDict::new(&dict);
+        assert_eq!(patterns.matches("xyz").len(), 3);
+        assert_eq!(patterns.matches("q"), &["a", "i"]);
+
+        // "the cat sees the tree" with t -> z, h -> k, e -> r, ...
+        let keys = solve("zkr eqz grrg zkr znrr", &patterns, 10);
+        assert!(!keys.is_empty());
+        let key = keys[0];
+        let decrypt = |c: char| (b'a' + key[(c as u8 - b'a') as usize].unwrap()) as char;
+        let plain: String = "zkreqzgrrgznrr".chars().map(decrypt).collect();
+        assert_eq!(plain, "thecatseestree");
+    }
+}
@@ -0,0 +1,441 @@
+#![allow(dead_code)]
+
+use std::fmt;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use rayon::prelude::*;
+
+use crate::{
+    alpha,
+    fitness::Fitness,
+    simp_sub::{self, AnnealSchedule},
+};
+
+const I: u8 = 8;
+const J: u8 = 9;
+const X: u8 = 23;
+const Q: u8 = 16;
+
+/// Letters that fill a Playfair square: the alphabet without J
+const NO_J: [u8; 25] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+];
+
+/// Fewest letters for identification to fully trust that a text looks like Playfair; on shorter texts annealing
+/// finds squares that decrypt to English-looking gibberish
+pub const MIN_AUTO_LETTERS: usize = 200;
+
+/// Annealing schedule of `Playfair::crack()`; the key space is much bumpier than simple substitution's
+pub const SCHEDULE: AnnealSchedule = AnnealSchedule {
+    temp: 0.03,
+    cooling: 0.999998,
+    iterations: 600_000,
+    restarts: 4,
+};
+
+/// Playfair cipher: letters are encrypted in pairs using a 5x5 square of the alphabet, with I and J sharing a cell.
+/// A pair in the same row is replaced by the letters to their right, a pair in the same column by the letters
+/// below them, and any other pair by the letters in the other corners of their rectangle, on the same rows.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Playfair {
+    /// Letter positions (0 for 'a'), row by row; never has J
+    square: [u8; 25],
+    /// Cell in `square` of every letter; J has the cell of I
+    cell: [u8; 26],
+}
+
+impl fmt::Debug for Playfair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Playfair---")?;
+        for row in self.square_str().as_bytes().chunks(5) {
+            writeln!(f, "{}", String::from_utf8_lossy(row))?;
+        }
+        Ok(())
+    }
+}
+
+impl Playfair {
+    /// Square starting with the letters of `keyword` in order, skipping repeats, then the rest of the alphabet.
+    /// J counts as I, and chars other than ascii letters are ignored.
+    pub fn new(keyword: &str) -> Self {
+        let mut used = [false; 26];
+        used[J as usize] = true;
+        let mut square = [0u8; 25];
+        let letters = keyword
+            .chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .map(|pos| if pos == J { I } else { pos })
+            .chain(NO_J.iter().cloned());
+        let mut len = 0;
+        for pos in letters {
+            if !used[pos as usize] {
+                used[pos as usize] = true;
+                square[len] = pos;
+                len += 1;
+            }
+        }
+        Self::from_letters(square)
+    }
+
+    /// Square from its letters, row by row; must have every letter but J exactly once
+    pub fn from_square(square: &str) -> Result<Self, &'static str> {
+        let letters: Option<Vec<u8>> = square
+            .chars()
+            .map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .collect();
+        let mut sorted =
+            letters.ok_or("Error creating a key for Playfair: square must only contain letters")?;
+        let mut cells = [0u8; 25];
+        if sorted.len() == 25 {
+            cells.copy_from_slice(&sorted);
+        }
+        sorted.sort_unstable();
+        if sorted != NO_J {
+            return Err(
+                "Error creating a key for Playfair: square must have every letter but j once",
+            );
+        }
+        Ok(Self::from_letters(cells))
+    }
+
+    fn from_letters(square: [u8; 25]) -> Self {
+        let mut key = Self {
+            square,
+            cell: [0; 26],
+        };
+        key.update_cells();
+        key
+    }
+
+    fn update_cells(&mut self) {
+        for (cell, pos) in self.square.iter().enumerate() {
+            self.cell[*pos as usize] = cell as u8;
+        }
+        self.cell[J as usize] = self.cell[I as usize];
+    }
+
+    /// The square as 25 lowercase letters, row by row
+    pub fn square_str(&self) -> String {
+        self.square.iter().map(|pos| (b'a' + pos) as char).collect()
+    }
+
+    /// Split the letters of text into the pairs that get encrypted, as letter positions. J becomes I, an X goes
+    /// between two same letters that would fall in one pair, and an X pads an odd last letter; a Q is used
+    /// instead where the letter is X itself.
+    pub fn prepare(text: &str) -> Vec<u8> {
+        let letters: Vec<u8> = text
+            .chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .map(|pos| if pos == J { I } else { pos })
+            .collect();
+        let filler = |pos: u8| if pos == X { Q } else { X };
+
+        let mut pairs = Vec::with_capacity(letters.len() + letters.len() / 4);
+        let mut rest = letters.as_slice();
+        while let Some((&first, tail)) = rest.split_first() {
+            match tail.first() {
+                Some(&second) if second != first => {
+                    pairs.extend_from_slice(&[first, second]);
+                    rest = &tail[1..];
+                }
+                _ => {
+                    pairs.extend_from_slice(&[first, filler(first)]);
+                    rest = tail;
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Encrypt the letters of text as lowercase letters, after `prepare()`
+    pub fn encrypt(&self, text: &str) -> String {
+        let mut out = vec![];
+        self.map_letters(&Self::prepare(text), 1, &mut out);
+        out.iter().map(|pos| (b'a' + pos) as char).collect()
+    }
+
+    /// Decrypt the letters of text, keeping their case; everything else is dropped. The fillers that `prepare()`
+    /// added are left in. Assumes `check()` passes on text.
+    pub fn decrypt(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().filter(char::is_ascii_alphabetic).collect();
+        let letters: Vec<u8> = chars
+            .iter()
+            .map(|c| c.to_ascii_lowercase() as u8 - b'a')
+            .collect();
+        let mut out = vec![];
+        self.map_letters(&letters, 4, &mut out);
+        out.iter()
+            .zip(chars)
+            .map(|(pos, c)| alpha::match_case((b'a' + pos) as char, c))
+            .collect()
+    }
+
+    /// Drop the likely fillers from a decryption: an X ending a pair between two equal letters, as in "balxloon",
+    /// and a final X. A real X in such a spot, as in "exe", goes too.
+    pub fn remove_fillers(plain: &str) -> String {
+        let chars: Vec<char> = plain.chars().collect();
+        let is_filler = |i: usize| {
+            i % 2 == 1
+                && chars[i].eq_ignore_ascii_case(&'x')
+                && (i + 1 == chars.len() || chars[i - 1].eq_ignore_ascii_case(&chars[i + 1]))
+        };
+        chars
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !is_filler(*i))
+            .map(|(_, c)| c)
+            .collect()
+    }
+
+    /// Encrypt (`shift` 1) or decrypt (`shift` 4) letter positions pair by pair into `out`; an odd last letter is
+    /// dropped
+    fn map_letters(&self, letters: &[u8], shift: u8, out: &mut Vec<u8>) {
+        out.clear();
+        for pair in letters.chunks_exact(2) {
+            let (c0, c1) = (self.cell[pair[0] as usize], self.cell[pair[1] as usize]);
+            let (r0, k0, r1, k1) = (c0 / 5, c0 % 5, c1 / 5, c1 % 5);
+            let (d0, d1) = if r0 == r1 {
+                (r0 * 5 + (k0 + shift) % 5, r1 * 5 + (k1 + shift) % 5)
+            } else if k0 == k1 {
+                (((r0 + shift) % 5) * 5 + k0, ((r1 + shift) % 5) * 5 + k1)
+            } else {
+                (r0 * 5 + k1, r1 * 5 + k0)
+            };
+            out.push(self.square[d0 as usize]);
+            out.push(self.square[d1 as usize]);
+        }
+    }
+
+    /// Reject text that can't be Playfair ciphertext: an odd number of letters, a J, or the same letter twice in
+    /// a pair
+    pub fn check(text: &str) -> Result<(), &'static str> {
+        let letters: Vec<u8> = text
+            .chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .collect();
+        if letters.is_empty() || !letters.len().is_multiple_of(2) {
+            return Err("not playfair: needs an even number of letters");
+        }
+        if letters.contains(&J) {
+            return Err("not playfair: has the letter j");
+        }
+        if letters.chunks_exact(2).any(|pair| pair[0] == pair[1]) {
+            return Err("not playfair: has a pair of the same letter");
+        }
+        Ok(())
+    }
+
+    /// Crack text by simulated annealing over key squares, from `schedule.restarts` random squares, and return
+    /// the best key with its score. Only the n-gram part of `fitness` applies, since word boundaries are lost.
+    /// Fails if text can't be Playfair, or if even the best square does not decrypt to plausible English.
+    ///
+    /// Each restart has its own rng derived from `seed`, so the same seed always gives the same key.
+    pub fn crack(
+        text: &str,
+        fitness: &Fitness,
+        schedule: &AnnealSchedule,
+        seed: u64,
+    ) -> Result<(Self, f64), &'static str> {
+        Self::check(text)?;
+        let letters: Vec<u8> = text
+            .chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .collect();
+
+        let restarts: Vec<(Self, f64)> = simp_sub::worker_rngs(seed, schedule.restarts.max(1))
+            .into_par_iter()
+            .map(|mut rng| anneal(&letters, fitness, schedule, &mut rng))
+            .collect();
+        // ties go to the earlier restart
+        let (cipher, score) = restarts
+            .into_iter()
+            .fold(None, |best: Option<(Self, f64)>, cur| match best {
+                Some(best) if best.1 >= cur.1 => Some(best),
+                _ => Some(cur),
+            })
+            .unwrap();
+        if !fitness.is_plausible(&cipher.decrypt(text)) {
+            return Err("no playfair square decrypts to plausible English");
+        }
+        Ok((cipher, score))
+    }
+
+    /// Change the square a little: mostly a swap of two letters, sometimes a swap of two rows or columns, or a
+    /// flip of the whole square, which are hard to reach with swaps of letters
+    fn perturb<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        match rng.gen_range(0, 50) {
+            0 => {
+                let (a, b) = two_of_five(rng);
+                for k in 0..5 {
+                    self.square.swap(a * 5 + k, b * 5 + k);
+                }
+            }
+            1 => {
+                let (a, b) = two_of_five(rng);
+                for r in 0..5 {
+                    self.square.swap(r * 5 + a, r * 5 + b);
+                }
+            }
+            // upside down
+            2 => {
+                for r in 0..2 {
+                    for k in 0..5 {
+                        self.square.swap(r * 5 + k, (4 - r) * 5 + k);
+                    }
+                }
+            }
+            // mirrored
+            3 => self.square.chunks_mut(5).for_each(|row| row.reverse()),
+            // rotated by half a turn
+            4 => self.square.reverse(),
+            _ => {
+                let a = rng.gen_range(0, 25);
+                let b = (a + rng.gen_range(1, 25)) % 25;
+                self.square.swap(a, b);
+            }
+        }
+        self.update_cells();
+    }
+}
+
+/// Two different numbers below 5
+fn two_of_five<R: Rng + ?Sized>(rng: &mut R) -> (usize, usize) {
+    let a = rng.gen_range(0, 5);
+    (a, (a + rng.gen_range(1, 5)) % 5)
+}
+
+/// One annealing run from a random square; see `simp_sub::anneal()`
+fn anneal(
+    letters: &[u8],
+    fitness: &Fitness,
+    schedule: &AnnealSchedule,
+    rng: &mut StdRng,
+) -> (Playfair, f64) {
+    let mut square = NO_J;
+    square.shuffle(rng);
+    let mut key = Playfair::from_letters(square);
+
+    let mut temp = schedule.temp * letters.len() as f64;
+    let mut plain = Vec::with_capacity(letters.len());
+    key.map_letters(letters, 4, &mut plain);
+    let mut score = fitness.score_letters(&plain);
+    let mut best = (key.clone(), score);
+
+    for _ in 0..schedule.iterations {
+        let old = key.square;
+        key.perturb(rng);
+        key.map_letters(letters, 4, &mut plain);
+        let new_score = fitness.score_letters(&plain);
+        let delta = new_score - score;
+
+        if delta > 0. || rng.gen::<f64>() < (delta / temp).exp() {
+            score = new_score;
+            if score > best.1 {
+                best = (key.clone(), score);
+            }
+        } else {
+            key.square = old;
+            key.update_cells();
+        }
+        temp *= schedule.cooling;
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ngram::NGram;
+    use crate::test_text::PLAIN;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_square() {
+        let key = Playfair::new("playfair example");
+        assert_eq!("playfirexmbcdghknoqstuvwz", key.square_str());
+        assert_eq!(key, Playfair::from_square(&key.square_str()).unwrap());
+        assert_eq!(key, Playfair::new("PLAYFAJR EXAMPLE"));
+
+        assert!(Playfair::from_square("abcdefghijklmnopqrstuvwxy").is_err());
+        assert!(Playfair::from_square("abcdefghiklmnopqrstuvwxy").is_err());
+        assert!(Playfair::from_square("abcdefghiklmnopqrstuvwxy1").is_err());
+    }
+
+    #[test]
+    fn test_prepare() {
+        let pairs = |text| -> String {
+            Playfair::prepare(text)
+                .iter()
+                .map(|pos| (b'a' + pos) as char)
+                .collect()
+        };
+        assert_eq!(
+            "hidethegoldinthetrexestump",
+            pairs("Hide the gold in the tree stump")
+        );
+        assert_eq!("iazxzx", pairs("jazz"));
+        assert_eq!("xqxq", pairs("xx"));
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = Playfair::new("playfair example");
+        let encrypted = key.encrypt("Hide the gold in the tree stump");
+        assert_eq!("bmodzbxdnabekudmuixmmouvif", encrypted);
+        assert_eq!(
+            "HIDETHEGOLDINTHETREXESTUMP",
+            key.decrypt(&encrypted.to_ascii_uppercase())
+        );
+    }
+
+    #[test]
+    fn test_remove_fillers() {
+        assert_eq!(Playfair::remove_fillers("BALXLOON"), "BALLOON");
+        assert_eq!(Playfair::remove_fillers("trexes"), "trees");
+        assert_eq!(Playfair::remove_fillers("excelx"), "excel");
+        assert_eq!(Playfair::remove_fillers("ax"), "a");
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(Playfair::check("BMODZBXDNABEKUDMUIXMMOUVIF").is_ok());
+        assert!(Playfair::check("BMODZ").is_err());
+        assert!(Playfair::check("BMODJB").is_err());
+        assert!(Playfair::check("BMOODB").is_err());
+        assert!(Playfair::check("").is_err());
+    }
+
+    #[test]
+    fn test_crack() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let key = Playfair::new("playfair example");
+        let text = key.encrypt(PLAIN);
+
+        // one restart is enough for a text this long, and keeps the test quick
+        let schedule = AnnealSchedule {
+            restarts: 1,
+            ..SCHEDULE
+        };
+        let (cracked, _score) = Playfair::crack(&text, &fitness, &schedule, 1).unwrap();
+        assert_eq!(key.decrypt(&text), cracked.decrypt(&text));
+        assert!(Playfair::crack("abc", &fitness, &SCHEDULE, 1).is_err());
+
+        // random pairs of letters that could be Playfair, but decrypt to nothing
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut random = String::new();
+        while random.len() < 300 {
+            let (a, b) = two_of_five(&mut rng);
+            let row = rng.gen_range(0, 5);
+            random.push((b'a' + NO_J[row * 5 + a]) as char);
+            random.push((b'a' + NO_J[(row + 1) % 5 * 5 + b]) as char);
+        }
+        let schedule = AnnealSchedule {
+            iterations: 20_000,
+            ..schedule
+        };
+        assert!(Playfair::check(&random).is_ok());
+        assert!(Playfair::crack(&random, &fitness, &schedule, 1).is_err());
+    }
+}
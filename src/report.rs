@@ -0,0 +1,304 @@
+use std::{fmt, time::Duration};
+
+use serde_json::{json, Value};
+
+use crate::{
+    dict::Dict, identify::CipherFamily, keyword::KeywordMatch, segment, simp_sub::KeyFamily,
+};
+
+/// How results are printed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    /// A single JSON document on stdout; progress messages go to stderr
+    Json,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format: {}; expected text or json", name)),
+        }
+    }
+
+    /// Print a progress message without getting in the way of the result
+    pub fn progress(self, msg: &str) {
+        match self {
+            Format::Text => println!("{}", msg),
+            Format::Json => eprintln!("{}", msg),
+        }
+    }
+}
+
+/// Key of a solved cipher
+pub enum Key {
+    Caesar(i32),
+    Affine {
+        a: u8,
+        b: u8,
+    },
+    Vigenere(String),
+    Columnar(Vec<usize>),
+    /// The 25 letters of the square, row by row
+    Playfair(String),
+    /// The key matrix, row by row
+    Hill(Vec<Vec<u8>>),
+    /// The cipher symbols of every plain letter that has any, in alphabetical order
+    Homophonic(Vec<(char, Vec<String>)>),
+    SimpSub {
+        from: String,
+        to: String,
+        /// How the key was made, when it is a well known kind of key
+        family: Option<KeyFamily>,
+        /// Dictionary words that make most of the key as keyword alphabets, best first, if asked for
+        keywords: Vec<KeywordMatch>,
+    },
+}
+
+impl Key {
+    pub fn cipher_name(&self) -> &'static str {
+        match self {
+            Key::Caesar(_) => "caesar",
+            Key::Affine { .. } => "affine",
+            Key::Vigenere(_) => "vigenere",
+            Key::Columnar(_) => "columnar",
+            Key::Playfair(_) => "playfair",
+            Key::Hill(_) => "hill",
+            Key::Homophonic(_) => "homophonic",
+            Key::SimpSub { .. } => "simple_substitution",
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Key::Caesar(shift) => json!({ "shift": shift }),
+            Key::Affine { a, b } => json!({ "a": a, "b": b }),
+            Key::Vigenere(key) => json!({ "keyword": key }),
+            Key::Columnar(order) => json!({ "column_order": order }),
+            Key::Playfair(square) => json!({ "square": square }),
+            Key::Hill(rows) => json!({ "matrix": rows }),
+            Key::Homophonic(homophones) => json!({
+                "homophones": homophones
+                    .iter()
+                    .map(|(letter, symbols)| (letter.to_string(), json!(symbols)))
+                    .collect::<serde_json::Map<_, _>>(),
+            }),
+            Key::SimpSub {
+                from,
+                to,
+                family,
+                keywords,
+            } => json!({
+                "from": from,
+                "to": to,
+                "family": family.as_ref().map(ToString::to_string),
+                "keyword": family.as_ref().and_then(KeyFamily::keyword),
+                "keyword_candidates": keywords
+                    .iter()
+                    .map(|found| json!({
+                        "keyword": found.keyword,
+                        "direction": found.direction.to_string(),
+                        "offset": found.offset,
+                        "reversed": found.reversed,
+                        "explained": found.explained,
+                        "known": found.known,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Caesar(shift) => writeln!(f, "caesar shift: {}", shift),
+            Key::Affine { a, b } => writeln!(f, "affine key: a = {}, b = {}", a, b),
+            Key::Vigenere(key) => writeln!(f, "vigenere key: {}", key),
+            Key::Columnar(order) => writeln!(
+                f,
+                "columnar order: {}",
+                order
+                    .iter()
+                    .map(|c| (c + 1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Key::Playfair(square) => writeln!(
+                f,
+                "playfair square: {}",
+                square
+                    .as_bytes()
+                    .chunks(5)
+                    .map(|row| String::from_utf8_lossy(row))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Key::Hill(rows) => writeln!(
+                f,
+                "hill matrix: {}",
+                rows.iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|x| x.to_string())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" / ")
+            ),
+            Key::Homophonic(homophones) => {
+                writeln!(f, "homophones:")?;
+                for (letter, symbols) in homophones {
+                    writeln!(f, "  {}: {}", letter, symbols.join(" "))?;
+                }
+                Ok(())
+            }
+            Key::SimpSub {
+                from,
+                to,
+                family,
+                keywords,
+            } => {
+                writeln!(f, "from: {}", from)?;
+                writeln!(f, "  to: {}", to)?;
+                if let Some(family) = family {
+                    writeln!(f, "key family: {}", family)?;
+                }
+                for found in keywords {
+                    writeln!(f, "keyword candidate: {}", found)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A cracked ciphertext
+pub struct Solution {
+    pub key: Key,
+    pub plaintext: String,
+    /// Quadgram log probability of the plaintext
+    pub score: f64,
+    /// Number of hill climbs or annealing restarts, and how many of them reached the best score, for the
+    /// crackers that use them
+    pub hills: Option<(usize, usize)>,
+    /// Seed of the random search, for the crackers that use one; passing it to --seed repeats the run
+    pub seed: Option<u64>,
+    pub elapsed: Duration,
+    /// Runners-up with different plaintexts, best first, for when the best score is not the right answer
+    pub alternatives: Vec<Alternative>,
+}
+
+/// A key that scored below the best one
+pub struct Alternative {
+    pub key: Key,
+    pub plaintext: String,
+    pub score: f64,
+}
+
+impl Solution {
+    /// Split the plaintexts of a text without word breaks into dictionary words; see `segment::segment()`
+    pub fn segment(&mut self, dict: &Dict) {
+        self.plaintext = segment::segment(&self.plaintext, dict);
+        for alternative in self.alternatives.iter_mut() {
+            alternative.plaintext = segment::segment(&alternative.plaintext, dict);
+        }
+    }
+
+    /// Print the solution; `ranking` is the output of `identify` that led to it
+    pub fn print(&self, format: Format, ranking: &[(CipherFamily, f64)]) {
+        match format {
+            Format::Text => {
+                print!("{}", self.key);
+                println!(
+                    "Best result: {}, quad_gram_score: {}",
+                    self.plaintext, self.score
+                );
+                if let Some((hills, best_hits)) = self.hills {
+                    println!("hills: {}, reached the best score: {}", hills, best_hits);
+                }
+                if let Some(seed) = self.seed {
+                    println!("seed: {}", seed);
+                }
+                println!("time taken: {:?}", self.elapsed);
+                for (i, alt) in self.alternatives.iter().enumerate() {
+                    println!("--- candidate {}, quad_gram_score: {}", i + 2, alt.score);
+                    print!("{}", alt.key);
+                    println!("{}", alt.plaintext);
+                }
+            }
+            Format::Json => {
+                let doc = json!({
+                    "cipher": self.key.cipher_name(),
+                    "key": self.key.to_json(),
+                    "plaintext": self.plaintext,
+                    "score": self.score,
+                    "hills": self.hills.map(|h| h.0),
+                    "best_hits": self.hills.map(|h| h.1),
+                    "seed": self.seed,
+                    "elapsed_secs": self.elapsed.as_secs_f64(),
+                    "identified": ranking_to_json(ranking),
+                    "alternatives": self
+                        .alternatives
+                        .iter()
+                        .map(|alt| json!({
+                            "key": alt.key.to_json(),
+                            "plaintext": alt.plaintext,
+                            "score": alt.score,
+                        }))
+                        .collect::<Vec<_>>(),
+                });
+                println!("{}", serde_json::to_string_pretty(&doc).unwrap());
+            }
+        }
+    }
+}
+
+fn ranking_to_json(ranking: &[(CipherFamily, f64)]) -> Value {
+    ranking
+        .iter()
+        .map(|(family, confidence)| {
+            json!({ "family": family.to_string(), "confidence": confidence })
+        })
+        .collect()
+}
+
+/// Print letter frequencies from `caesar::order_percentage()`, most frequent first
+pub fn print_frequencies(format: Format, order: &[(char, f64)]) {
+    match format {
+        Format::Text => println!("Percent: {:?}", order),
+        Format::Json => {
+            let frequencies: Vec<Value> = order
+                .iter()
+                .map(|(letter, percent)| json!({ "letter": letter, "percent": percent }))
+                .collect();
+            let doc = json!({ "frequencies": frequencies });
+            println!("{}", serde_json::to_string_pretty(&doc).unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(Format::parse("json"), Ok(Format::Json));
+        assert_eq!(Format::parse("text"), Ok(Format::Text));
+        assert!(Format::parse("xml").is_err());
+    }
+
+    #[test]
+    fn test_key_json() {
+        assert_eq!(Key::Caesar(3).to_json(), json!({ "shift": 3 }));
+        assert_eq!(
+            Key::Columnar(vec![2, 0, 1]).to_json(),
+            json!({ "column_order": [2, 0, 1] })
+        );
+        assert_eq!(Key::Vigenere("lemon".to_owned()).cipher_name(), "vigenere");
+    }
+}
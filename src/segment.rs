@@ -0,0 +1,197 @@
+#![allow(dead_code)]
+
+//! Texts without word breaks: telling them apart, and putting the spaces back into their plaintext
+
+use std::collections::HashMap;
+
+use crate::dict::Dict;
+
+/// Longest average length of the chunks of a text with word breaks
+const MAX_MEAN_WORD_LEN: usize = 12;
+
+/// Fewest whitespace separated chunks in a text before equal length chunks are taken as fixed size groups
+const MIN_GROUPS: usize = 4;
+
+/// Whether the whitespace of `text` separates words. False if there is none, if the chunks are too long on
+/// average to be words (like lines of letters), or if every chunk but the last has the same length, the way
+/// ciphertexts are often sent in groups of 5 letters.
+pub fn has_word_breaks(text: &str) -> bool {
+    let lens: Vec<usize> = text
+        .split_whitespace()
+        .map(|chunk| chunk.chars().filter(char::is_ascii_alphabetic).count())
+        .filter(|len| *len > 0)
+        .collect();
+    match lens.split_last() {
+        None | Some((_, [])) => false,
+        Some(_) if lens.iter().sum::<usize>() > MAX_MEAN_WORD_LEN * lens.len() => false,
+        Some((last, rest)) => {
+            let grouped = lens.len() >= MIN_GROUPS
+                && rest.iter().all(|len| *len == rest[0])
+                && *last <= rest[0];
+            !grouped
+        }
+    }
+}
+
+/// Longest word tried when segmenting
+const MAX_WORD_LEN: usize = 24;
+
+/// About 1000 common English words, one per line, most frequent first. The word list has no frequencies, and
+/// many of its short words are rare.
+const COMMON_WORDS: &str = include_str!("../common_words.txt");
+
+/// Endings tried on a word that is not in `COMMON_WORDS` itself, with what to put back to get the listed word
+const ENDINGS: [(&str, &str); 10] = [
+    ("s", ""),
+    ("es", ""),
+    ("ed", ""),
+    ("ed", "e"),
+    ("d", ""),
+    ("ing", ""),
+    ("ing", "e"),
+    ("ly", ""),
+    ("er", ""),
+    ("r", ""),
+];
+
+/// How many times rarer a word with one of `ENDINGS` is taken to be than the listed word
+const ENDING_RANK_FACTOR: usize = 4;
+
+/// Log10 probability of a dictionary word that is not common
+const RARE_WORD: f64 = -6.;
+
+/// Log10 probability of a letter that is not part of any dictionary word
+const UNKNOWN_LETTER: f64 = -5.;
+
+/// The letters of `text` split into dictionary words, separated by single spaces. Other chars are dropped and
+/// letters keep their case.
+///
+/// Picks the most likely sequence of words, taking the word at rank r of `COMMON_WORDS` to have probability
+/// 0.1 / r (Zipf's law), and any other dictionary word to be rare. That prefers "is a really big" to "is areally
+/// big", and "even though the" to "even thought he", where the word list alone cannot tell them apart. Letters
+/// that fit no word are kept together as one chunk.
+pub fn segment(text: &str, dict: &Dict) -> String {
+    let letters: Vec<char> = text.chars().filter(char::is_ascii_alphabetic).collect();
+    let lower: String = letters.iter().map(|c| c.to_ascii_lowercase()).collect();
+    let n = letters.len();
+    let ranks: HashMap<&str, usize> = COMMON_WORDS
+        .lines()
+        .enumerate()
+        .map(|(i, word)| (word, i + 1))
+        .collect();
+
+    // best[i]: best log10 probability of lower[..i], and where its last word starts (None for an unknown letter)
+    let mut best: Vec<(f64, Option<usize>)> = vec![(0., None); n + 1];
+    for end in 1..=n {
+        best[end] = (best[end - 1].0 + UNKNOWN_LETTER, None);
+        for start in end.saturating_sub(MAX_WORD_LEN)..end {
+            let word = &lower[start..end];
+            if is_word(dict, word) {
+                let score = best[start].0 + word_log_prob(&ranks, word);
+                if score > best[end].0 {
+                    best[end] = (score, Some(start));
+                }
+            }
+        }
+    }
+
+    let mut chunks: Vec<(usize, usize, bool)> = vec![];
+    let mut end = n;
+    while end > 0 {
+        match best[end].1 {
+            Some(start) => {
+                chunks.push((start, end, true));
+                end = start;
+            }
+            None => {
+                // join unknown letters into the chunk after them
+                match chunks.last_mut() {
+                    Some((start, _, false)) => *start = end - 1,
+                    _ => chunks.push((end - 1, end, false)),
+                }
+                end -= 1;
+            }
+        }
+    }
+
+    chunks
+        .iter()
+        .rev()
+        .map(|(start, end, _)| letters[*start..*end].iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Log10 probability of a dictionary word, by its rank in `COMMON_WORDS` or that of the word it ends
+fn word_log_prob(ranks: &HashMap<&str, usize>, word: &str) -> f64 {
+    let rank = ranks.get(word).copied().or_else(|| {
+        ENDINGS.iter().find_map(|(ending, replacement)| {
+            let stem = word.strip_suffix(ending)?;
+            let rank = ranks.get(format!("{}{}", stem, replacement).as_str())?;
+            Some(rank * ENDING_RANK_FACTOR)
+        })
+    });
+    match rank {
+        Some(rank) => (0.1 / rank as f64).log10(),
+        None => RARE_WORD,
+    }
+}
+
+/// Single letters other than "a" and "i" are in the word list, but hardly ever words
+fn is_word(dict: &Dict, word: &str) -> bool {
+    match word {
+        "a" | "i" => true,
+        _ => word.len() > 1 && dict.contains(word),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_word_breaks() {
+        assert!(has_word_breaks("the cat sat on the mat"));
+        assert!(has_word_breaks("attack at dawn"));
+        assert!(!has_word_breaks("thecatsatonthemat"));
+        assert!(!has_word_breaks("THECA TSATO NTHEM AT"));
+        assert!(!has_word_breaks("theca tsato nthem atxxx\n"));
+        assert!(!has_word_breaks(""));
+        assert!(!has_word_breaks(
+            "lifeisalwaysarichandsteady\ntimewhenyouarewaitingfor\n"
+        ));
+    }
+
+    #[test]
+    fn test_segment() {
+        let words = crate::data::read_words(None).unwrap();
+        let dict = Dict::new(words.lines().collect());
+        assert_eq!(
+            segment("LIFEI SALWA YSARI CHAND STEAD Y", &dict),
+            "LIFE IS ALWAYS A RICH AND STEADY"
+        );
+        assert_eq!(
+            segment("thesimplesubstitutioncipherisquiteeasytobreak", &dict),
+            "the simple substitution cipher is quite easy to break"
+        );
+        assert_eq!(segment("xqzjqistheway", &dict), "xqzjq is the way");
+    }
+
+    #[test]
+    fn test_segment_plaintexts() {
+        let words = crate::data::read_words(None).unwrap();
+        let dict = Dict::new(words.lines().collect());
+        // the plaintexts of sample3 and sample6, and lines where scoring words by length went wrong
+        for plaintext in &[
+            "the simple substitution cipher is quite easy to break even though the number of keys is a really big \
+             number there is a lot of redundancy and other statistical properties of english text that make it \
+             quite easy to determine a reasonably good key",
+            "LIFE IS ALWAYS A RICH AND STEADY TIME WHEN YOU ARE WAITING FOR SOMETHING TO HAPPEN OR HATCH",
+            "it was the best of times it was the worst of times",
+            "we hold these truths to be self evident that all men are created equal",
+            "the enemy will attack the northern bridge at noon tomorrow",
+        ] {
+            assert_eq!(segment(&plaintext.replace(' ', ""), &dict), *plaintext);
+        }
+    }
+}
@@ -1,13 +1,13 @@
 #![allow(dead_code)]
 
-use rand::{prelude::ThreadRng, seq::SliceRandom};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 
 #[derive(Clone)]
 pub struct SimpSub {
-    // map_keys: Vec<char>,
-    mapper: HashMap<char, char, BuildCharHasher>,
-    /// Useful for frequency based cracking
-    rev_mapper: HashMap<char, char, BuildCharHasher>,
+    /// `mapper[p]` is the cipher letter for plain letter `p`; letters are stored as positions in the alphabet
+    mapper: [u8; 26],
+    /// Inverse of `mapper`; useful for frequency based cracking
+    rev_mapper: [u8; 26],
 }
 
 impl fmt::Debug for SimpSub {
@@ -24,119 +24,232 @@ use std::{
     collections::HashMap,
     collections::HashSet,
     fmt,
-    hash::{BuildHasher, Hasher},
+    str::FromStr,
+    time::{Duration, Instant},
 };
 
-use crate::{alpha, ngram::NGram};
+use crate::{
+    affine::Affine,
+    alpha,
+    fitness::Fitness,
+    pattern::{self, PartialKey, PatternDict},
+};
 
 use rayon::prelude::*;
 
-/// Return byte value as hash
-struct CharHasher(u64);
-
-impl Hasher for CharHasher {
-    fn finish(&self) -> u64 {
-        self.0 as u64
-    }
-
-    fn write(&mut self, bytes: &[u8]) {
-        // we don't care about overflow, because we're only encoding ascii alphabets; they should all be encoded
-        // within the first byte
-        for byte in bytes {
-            self.0 += *byte as u64
-        }
-    }
-}
-
-#[derive(Clone)]
-struct BuildCharHasher;
+/// Fewest letters that must follow the keyword in alphabetical order for `SimpSub::family()` to call a key a
+/// keyword alphabet; with fewer, shuffled keys often pass by chance
+const MIN_KEYWORD_TAIL: usize = 10;
 
-impl BuildHasher for BuildCharHasher {
-    type Hasher = CharHasher;
-
-    fn build_hasher(&self) -> Self::Hasher {
-        CharHasher(0)
-    }
-}
+/// The identity permutation of the alphabet
+const IDENTITY: [u8; 26] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+];
 
 impl SimpSub {
+    /// Letters of `from` are encrypted to the letters of `to` at the same position. Letters missing from `from`
+    /// map to themselves; the resulting key must still be a one-to-one mapping.
     pub fn new(from: &str, to: &str) -> Result<Self, &'static str> {
         if from.len() != to.len() {
             return Err("Error creating a key for SimpSub: from.len() must match to.len()");
         }
 
-        let mut map_keys = Vec::with_capacity(from.len());
-        let mut mapper: HashMap<char, char, BuildCharHasher> =
-            HashMap::with_capacity_and_hasher(from.len(), BuildCharHasher);
-        let mut rev_mapper: HashMap<char, char, BuildCharHasher> =
-            HashMap::with_capacity_and_hasher(from.len(), BuildCharHasher);
+        let mut mapper = IDENTITY;
         for pair in from.chars().zip(to.chars()) {
-            mapper.insert(pair.0, pair.1);
-            rev_mapper.insert(pair.1, pair.0);
-            map_keys.push(pair.0);
+            match (
+                alpha::get_letter_pos(pair.0.to_ascii_lowercase()),
+                alpha::get_letter_pos(pair.1.to_ascii_lowercase()),
+            ) {
+                (Some(plain), Some(crypt)) => mapper[plain as usize] = crypt,
+                _ => return Err("Error creating a key for SimpSub: key must only contain letters"),
+            }
+        }
+
+        let mut rev_mapper = [26u8; 26];
+        for (plain, crypt) in mapper.iter().enumerate() {
+            if rev_mapper[*crypt as usize] != 26 {
+                return Err("Error creating a key for SimpSub: two letters map to the same letter");
+            }
+            rev_mapper[*crypt as usize] = plain as u8;
         }
 
         Ok(Self { mapper, rev_mapper })
     }
 
     /// A new SimpSub with random key
-    fn with_rand_key() -> Self {
-        let mut rand_key_arr = alpha::ASCII_LOWER;
-        rand_key_arr.shuffle(&mut rand::thread_rng());
-        Self::new(
-            alpha::ASCII_LOWER_STR,
-            &rand_key_arr.iter().collect::<String>(),
-        )
-        .unwrap()
+    pub fn with_rand_key<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut mapper = IDENTITY;
+        mapper.shuffle(rng);
+        Self::from_mapper(mapper)
     }
 
-    pub fn swap_letters_at(&self, k0: char, k1: char) -> Self {
-        let mut new_mapper = self.mapper.clone();
-        let v0 = new_mapper.get(&k0).unwrap().to_owned();
-        let v1 = new_mapper.get(&k1).unwrap().to_owned();
-        new_mapper.insert(k0, v1);
-        new_mapper.insert(k1, v0);
+    /// Atbash: the alphabet reversed, a <-> z, b <-> y, ...
+    pub fn atbash() -> Self {
+        Self::affine(25, 25).unwrap()
+    }
 
-        let mut new_rev_mapper = self.rev_mapper.clone();
-        new_rev_mapper.insert(v1, k0);
-        new_rev_mapper.insert(v0, k1);
+    /// Caesar cipher shifting forward by `shift`
+    pub fn caesar(shift: i32) -> Self {
+        Self::affine(1, shift).unwrap()
+    }
 
-        Self {
-            // map_keys: self.map_keys.clone(),
-            mapper: new_mapper,
-            rev_mapper: new_rev_mapper,
+    /// Affine key encrypting plain letter x as (a * x + b) mod 26; `a` must be coprime with 26
+    pub fn affine(a: i32, b: i32) -> Result<Self, &'static str> {
+        let cipher = Affine::new(a, b)?;
+        let mut mapper = [0u8; 26];
+        for (plain, crypt) in mapper.iter_mut().enumerate() {
+            *crypt = ((cipher.a() as usize * plain + cipher.b() as usize) % 26) as u8;
         }
+        Ok(Self::from_mapper(mapper))
     }
 
-    pub fn swap_rand_letters(&self, rng: &mut ThreadRng) -> Self {
-        // let keys: Vec<&char> = self.map_keys.choose_multiple(&mut rand::thread_rng(), 2).collect();
-        let keys: Vec<&char> = self.mapper.keys().collect();
-        let pair: Vec<&&char> = keys.choose_multiple(rng, 2).collect();
-        let (k0, k1) = (**pair[0], **pair[1]);
+    /// Keyword alphabet: the letters of `keyword` in order, skipping repeats, then the rest of the alphabet in
+    /// order, all of it written backwards if `reversed`. Plain letter `offset` (0 for 'a') encrypts to the first
+    /// letter of it, the next plain letter to the second, and so on around the alphabet. Chars other than ascii
+    /// letters are ignored.
+    ///
+    /// With no keyword, this is a Caesar shift back by `offset`, or Atbash when reversed.
+    pub fn keyword(keyword: &str, offset: usize, reversed: bool) -> Self {
+        let mut used = [false; 26];
+        let mut alphabet = Vec::with_capacity(26);
+        let letters = keyword
+            .chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .chain(IDENTITY.iter().cloned());
+        for pos in letters {
+            if !used[pos as usize] {
+                used[pos as usize] = true;
+                alphabet.push(pos);
+            }
+        }
+        if reversed {
+            alphabet.reverse();
+        }
 
-        self.swap_letters_at(k0, k1)
+        let mut mapper = [0u8; 26];
+        for (i, crypt) in alphabet.into_iter().enumerate() {
+            mapper[(i + offset) % 26] = crypt;
+        }
+        Self::from_mapper(mapper)
     }
 
-    fn key_as_str(&self) -> (String, String) {
-        let mut values: Vec<(&char, &char)> = self.mapper.iter().collect();
-        values.sort_unstable_by_key(|f| f.0);
-        let (from, to): (Vec<char>, Vec<char>) = values.iter().cloned().unzip();
+    /// Which of the constructors above makes this key, if any: the simplest of affine keys, then the keyword
+    /// alphabet with the shortest keyword, preferring no offset. Only the distinct letters of a keyword can be
+    /// recovered, in order, and letters at its end that happen to continue the rest of the alphabet are lost
+    /// to it.
+    pub fn family(&self) -> Option<KeyFamily> {
+        let b = self.mapper[0];
+        let a = (self.mapper[1] + 26 - b) % 26;
+        if let Ok(affine) = Self::affine(a as i32, b as i32) {
+            if affine.mapper == self.mapper {
+                return Some(match (a, b) {
+                    (1, _) => KeyFamily::Caesar(b),
+                    (25, 25) => KeyFamily::Atbash,
+                    _ => KeyFamily::Affine { a, b },
+                });
+            }
+        }
+
+        // a keyword starting with letters after the last of the rest of the alphabet gives the same key as the
+        // keyword without them, one offset on, so an offset counts as one more letter
+        let mut best: Option<(usize, KeyFamily)> = None;
+        for &reversed in [false, true].iter() {
+            for offset in 0..26 {
+                let mut alphabet: Vec<u8> =
+                    (0..26).map(|i| self.mapper[(i + offset) % 26]).collect();
+                if reversed {
+                    alphabet.reverse();
+                }
+                let len = match keyword_len(&alphabet) {
+                    Some(len) => len,
+                    None => continue,
+                };
+                let cost = len + (offset != 0) as usize;
+                // ties go to the earlier variant, unreversed and with the smaller offset
+                if best.as_ref().is_none_or(|best| cost < best.0) {
+                    let family = KeyFamily::Keyword {
+                        keyword: alphabet[..len]
+                            .iter()
+                            .map(|pos| (b'a' + pos) as char)
+                            .collect(),
+                        offset: offset as u8,
+                        reversed,
+                    };
+                    best = Some((cost, family));
+                }
+            }
+        }
+        best.map(|(_, family)| family)
+    }
+
+    /// Build a key from a permutation of letter positions
+    fn from_mapper(mapper: [u8; 26]) -> Self {
+        let mut rev_mapper = [0u8; 26];
+        for (plain, crypt) in mapper.iter().enumerate() {
+            rev_mapper[*crypt as usize] = plain as u8;
+        }
+        Self { mapper, rev_mapper }
+    }
+
+    /// Key that decrypts like `partial` where it is known; the other cipher letters get the unused plain letters
+    /// in alphabetical order
+    fn from_partial(partial: &PartialKey) -> Self {
+        let mut used = [false; 26];
+        for plain in partial.iter().flatten() {
+            used[*plain as usize] = true;
+        }
+        let mut unused = (0..26).filter(|plain| !used[*plain as usize]);
+
+        let mut rev_mapper = [0u8; 26];
+        for (crypt, plain) in partial.iter().enumerate() {
+            rev_mapper[crypt] = plain.unwrap_or_else(|| unused.next().unwrap());
+        }
+        let mut mapper = [0u8; 26];
+        for (crypt, plain) in rev_mapper.iter().enumerate() {
+            mapper[*plain as usize] = crypt as u8;
+        }
+        Self { mapper, rev_mapper }
+    }
+
+    /// Swap the cipher letters of plain letters `p0` and `p1` in place. Swapping the same pair again undoes it.
+    ///
+    /// Assumes both are positions in the alphabet
+    fn swap(&mut self, p0: u8, p1: u8) {
+        self.mapper.swap(p0 as usize, p1 as usize);
+        self.rev_mapper[self.mapper[p0 as usize] as usize] = p0;
+        self.rev_mapper[self.mapper[p1 as usize] as usize] = p1;
+    }
+
+    /// Swap the cipher letters of plain letters `k0` and `k1` in place; Assumes both are lower ascii letters
+    pub fn swap_letters_at(&mut self, k0: char, k1: char) {
+        self.swap(
+            alpha::get_letter_pos(k0).unwrap(),
+            alpha::get_letter_pos(k1).unwrap(),
+        )
+    }
+
+    /// Swap two random, different letters in place, and return their positions so the swap can be undone
+    pub fn swap_rand_letters<R: Rng + ?Sized>(&mut self, rng: &mut R) -> (u8, u8) {
+        let p0 = rng.gen_range(0, 26);
+        let p1 = (p0 + rng.gen_range(1, 26)) % 26;
+        self.swap(p0, p1);
+        (p0, p1)
+    }
+
+    pub fn key_as_str(&self) -> (String, String) {
         (
-            from.iter().collect::<String>(),
-            to.iter().collect::<String>(),
+            alpha::ASCII_LOWER_STR.to_owned(),
+            self.mapper.iter().map(|c| (b'a' + c) as char).collect(),
         )
     }
 
     #[allow(dead_code)]
-    /// Encrypt entire string with key; will turn all letters into lowercase
+    /// Encrypt entire string with key; letters keep their case and other chars are left unchanged
     pub fn encrypt(&self, text: &str) -> String {
         text.chars()
-            .map(|c| {
-                self.mapper
-                    .get(&c.to_ascii_lowercase())
-                    .or(Some(&c))
-                    .unwrap()
-                    .to_owned()
+            .map(|c| match alpha::get_letter_pos(c.to_ascii_lowercase()) {
+                Some(pos) => alpha::match_case((b'a' + self.mapper[pos as usize]) as char, c),
+                None => c,
             })
             .collect()
     }
@@ -146,11 +259,10 @@ impl SimpSub {
     }
 
     fn decrypt_char(&self, c: char) -> char {
-        *self
-            .rev_mapper
-            .get(&c.to_ascii_lowercase())
-            .or(Some(&c))
-            .unwrap()
+        match alpha::get_letter_pos(c.to_ascii_lowercase()) {
+            Some(pos) => alpha::match_case((b'a' + self.rev_mapper[pos as usize]) as char, c),
+            None => c,
+        }
     }
 
     #[allow(dead_code)]
@@ -167,24 +279,30 @@ impl SimpSub {
             .collect()
     }
 
-    /// Manually alter mapper; Assumes that `key` and `val` are both valid keys of mapper
-    ///
-    fn change_key(&mut self, key: char, val: char) {
-        let old_val = *self.mapper.get(&key).unwrap();
-
-        // so we want to swap val and old_val in our mapper
-        self.mapper.insert(key, val);
-
-        let key1 = *self.rev_mapper.get(&val).unwrap();
+    /// Decrypt words into letter positions for `NGram::score_letters()`; chars other than letters are dropped
+    fn decrypt_words_to_letters(&self, words: &[String]) -> Vec<u8> {
+        words
+            .iter()
+            .flat_map(|word| word.bytes())
+            .filter_map(|b| match b {
+                b'a'..=b'z' => Some(self.rev_mapper[(b - b'a') as usize]),
+                _ => None,
+            })
+            .collect()
+    }
 
-        self.mapper.insert(key1, old_val);
+    /// Manually alter mapper so that `key` encrypts to `val`, keeping the key one-to-one;
+    /// Assumes that `key` and `val` are both lower ascii letters
+    pub fn change_key(&mut self, key: char, val: char) {
+        let key = alpha::get_letter_pos(key).unwrap();
+        let val = alpha::get_letter_pos(val).unwrap();
 
-        // now update rev_mapper to match mapper
-        self.rev_mapper.insert(val, key);
-        self.rev_mapper.insert(old_val, key1);
+        // the plain letter currently encrypted to val takes over key's old cipher letter
+        let key1 = self.rev_mapper[val as usize];
+        self.swap(key, key1);
     }
 
-    fn with_key_change(&self, key: char, val: char) -> Self {
+    pub fn with_key_change(&self, key: char, val: char) -> Self {
         let mut new_cipher = self.clone();
         new_cipher.change_key(key, val);
         new_cipher
@@ -192,226 +310,669 @@ impl SimpSub {
 
     /// Change mapper (key) so that crypt text will match crib; This is a generalized version of `change_key()`
     ///
-    /// Assumes crypt.len() == crib.len()
-    fn match_crib(
+    /// Fails if a crypt letter would need two crib letters or the other way round, or if a plain letter in
+    /// `locked` would have to change. Assumes both are lower ascii letters.
+    pub fn match_crib(
         &self,
         crypt: &str,
         crib: &str,
-        char_used: &HashSet<char, BuildCharHasher>,
+        locked: &[bool; 26],
     ) -> Result<Self, &'static str> {
+        if crypt.len() != crib.len() {
+            return Err("crypt and crib must have the same length");
+        }
+
         let mut new_coder = self.clone();
-        let mem: HashMap<char, char, BuildCharHasher> = HashMap::with_hasher(BuildCharHasher);
+        let mut mem: HashMap<char, char> = HashMap::new();
+        let mut rev_mem: HashMap<char, char> = HashMap::new();
         for (crypt_c, crib_c) in crypt.chars().zip(crib.chars()) {
-            if char_used.contains(&crypt_c) {
-                return Err("crypt char already used");
-            }
-            if let Some(val) = mem.get(&crypt_c) {
-                if *val == crib_c {
-                    return Err("conflict in crypt and crib");
-                }
+            if *mem.entry(crypt_c).or_insert(crib_c) != crib_c
+                || *rev_mem.entry(crib_c).or_insert(crypt_c) != crypt_c
+            {
+                return Err("conflict in crypt and crib");
             }
 
-            new_coder.change_key(crypt_c, crib_c);
+            let plain = alpha::get_letter_pos(crib_c).unwrap();
+            let crypt_pos = alpha::get_letter_pos(crypt_c).unwrap();
+            // change_key() also moves the plain letter that currently encrypts to crypt_c
+            let displaced = new_coder.rev_mapper[crypt_pos as usize];
+            if new_coder.mapper[plain as usize] != crypt_pos
+                && (locked[plain as usize] || locked[displaced as usize])
+            {
+                return Err("crib conflicts with a locked letter");
+            }
+            new_coder.change_key(crib_c, crypt_c);
         }
         Ok(new_coder)
     }
 
-    /// Decrypt text and calculate log ngram score
-    fn de_score(&self, words: &[String], ngram: &NGram) -> f64 {
-        ngram.score_chars(&self.decrypt_words_to_chars(words))
+    /// This key, changed so the plain letters in `locked` encrypt as they do with `other`; the rest of the key
+    /// stays one-to-one
+    fn with_locked(mut self, other: &SimpSub, locked: &[bool; 26]) -> Self {
+        for (plain, _) in locked.iter().enumerate().filter(|(_, locked)| **locked) {
+            // the plain letter currently encrypted to the wanted letter takes over this one's
+            let displaced = self.rev_mapper[other.mapper[plain] as usize];
+            self.swap(plain as u8, displaced);
+        }
+        self
+    }
+
+    /// Decrypt text and score it; `words` are the ciphertext words
+    fn de_score(&self, words: &[String], fitness: &Fitness) -> f64 {
+        fitness.score_words(
+            &self.decrypt_words_to_letters(words),
+            words
+                .iter()
+                .map(|word| word.bytes().filter(u8::is_ascii_lowercase).count()),
+        )
+    }
+}
+
+/// Length of the keyword that starts `alphabet`, a permutation of letter positions, if at least
+/// `MIN_KEYWORD_TAIL` letters follow it
+///
+/// The letters after any prefix are the ones not in it, so the shortest keyword is everything before the
+/// increasing run at the end.
+fn keyword_len(alphabet: &[u8]) -> Option<usize> {
+    let run = alphabet
+        .windows(2)
+        .rev()
+        .take_while(|pair| pair[0] < pair[1])
+        .count()
+        + 1;
+    if run < MIN_KEYWORD_TAIL {
+        return None;
+    }
+    Some(alphabet.len() - run)
+}
+
+/// How a substitution key was made, as found by `SimpSub::family()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyFamily {
+    Atbash,
+    /// Shift forward
+    Caesar(u8),
+    Affine {
+        a: u8,
+        b: u8,
+    },
+    /// See `SimpSub::keyword()`
+    Keyword {
+        keyword: String,
+        offset: u8,
+        reversed: bool,
+    },
+}
+
+impl KeyFamily {
+    pub fn keyword(&self) -> Option<&str> {
+        match self {
+            KeyFamily::Keyword { keyword, .. } => Some(keyword),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for KeyFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyFamily::Atbash => write!(f, "atbash"),
+            KeyFamily::Caesar(shift) => write!(f, "caesar shift {}", shift),
+            KeyFamily::Affine { a, b } => write!(f, "affine a = {}, b = {}", a, b),
+            KeyFamily::Keyword {
+                keyword,
+                offset,
+                reversed,
+            } => {
+                write!(f, "keyword {}", keyword)?;
+                if *offset != 0 {
+                    write!(f, ", offset {}", offset)?;
+                }
+                if *reversed {
+                    write!(f, ", reversed")?;
+                }
+                Ok(())
+            }
+        }
     }
 }
 
-/// Build a frequency list of strings in ascending order
-fn freq_list(list: &[String]) -> Vec<(String, usize)> {
-    let mut map: HashMap<String, usize> = HashMap::new();
-    for word in list {
-        *map.entry(word.to_owned()).or_insert(0) += 1;
+/// Known plaintext for `crib_crack()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Crib {
+    /// Lower ascii letters only
+    pub text: String,
+    /// Where the crib starts among the letters of the ciphertext, counting from 0, if known
+    pub position: Option<usize>,
+}
+
+impl FromStr for Crib {
+    type Err = String;
+
+    /// `attack at dawn`, or `attack at dawn@40` to give the position; only the letters are kept
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (text, position) = match s.rfind('@') {
+            Some(at) => {
+                let position = s[at + 1..].trim().parse().map_err(|_| {
+                    format!("expected a letter position after @, got {}", &s[at + 1..])
+                })?;
+                (&s[..at], Some(position))
+            }
+            None => (s, None),
+        };
+
+        let text: String = text
+            .chars()
+            .filter(char::is_ascii_alphabetic)
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        if text.is_empty() {
+            return Err(format!("crib has no letters: {}", s));
+        }
+        Ok(Self { text, position })
     }
+}
 
-    let mut freqs: Vec<(String, usize)> = map.into_iter().collect();
-    freqs.sort_unstable_by_key(|e| e.1);
+/// A key found by one of the solvers
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub cipher: SimpSub,
+    pub score: f64,
+    pub plaintext: String,
+}
+
+/// Result of one of the random restart solvers
+#[derive(Clone, Debug)]
+pub struct CrackReport {
+    /// Best keys with different plaintexts, best first
+    pub candidates: Vec<Candidate>,
+    /// Number of hills climbed or annealing restarts run
+    pub hills: usize,
+    /// Number of those that reached the best score
+    pub best_hits: usize,
+}
 
-    freqs
+/// When `rand_crack` stops climbing new hills
+#[derive(Clone, Debug, PartialEq)]
+pub struct StopRule {
+    /// Most hills to climb
+    pub max_hills: usize,
+    /// Stop once this many hills have reached the best score found so far
+    pub confirmations: Option<usize>,
+    /// Stop once this much time has passed
+    pub budget: Option<Duration>,
 }
 
-/// Run a random guess & check crack
-pub fn rand_crack(text: &str, ngram: &NGram, nhills: usize) -> (SimpSub, f64) {
+impl StopRule {
+    /// Climb exactly `nhills` hills
+    pub fn hills(nhills: usize) -> Self {
+        Self {
+            max_hills: nhills,
+            confirmations: None,
+            budget: None,
+        }
+    }
+
+    fn is_adaptive(&self) -> bool {
+        self.confirmations.is_some() || self.budget.is_some()
+    }
+}
+
+/// Hills climbed in parallel between checks of an adaptive `StopRule`. Fixed, so that where the search stops
+/// does not depend on the number of threads.
+const HILL_BATCH: usize = 64;
+
+/// Run a random guess & check crack, climbing hills from random keys until `stop` says so, and return the `top`
+/// best keys that give different plaintexts
+///
+/// The starting keys are drawn in order from an rng seeded with `seed`, and ties go to the earlier hill, so the
+/// same seed always gives the same keys however rayon schedules the hills. Only a time budget makes it vary.
+pub fn rand_crack(
+    text: &str,
+    fitness: &Fitness,
+    stop: &StopRule,
+    seed: u64,
+    top: usize,
+) -> CrackReport {
+    let start = Instant::now();
     let lower = text.to_lowercase();
     let words: Vec<String> = lower.split_whitespace().map(alpha::rm_punct).collect();
 
-    let mut ciphers = Vec::with_capacity(nhills);
-    for _ in 0..nhills {
-        ciphers.push(SimpSub::with_rand_key());
+    let max_hills = stop.max_hills.max(1);
+    let batch = if stop.is_adaptive() {
+        HILL_BATCH
+    } else {
+        max_hills
+    };
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut hills: Vec<(SimpSub, f64)> = Vec::with_capacity(max_hills);
+    while hills.len() < max_hills {
+        let n = batch.min(max_hills - hills.len());
+        let ciphers: Vec<SimpSub> = (0..n).map(|_| SimpSub::with_rand_key(&mut rng)).collect();
+        hills.par_extend(
+            ciphers
+                .into_par_iter()
+                .map(|cipher| climb_words(cipher, &words, fitness, &NO_LOCKS)),
+        );
+
+        let confirmed = match stop.confirmations {
+            Some(n) => count_best(&hills) >= n,
+            None => false,
+        };
+        let out_of_time = match stop.budget {
+            Some(budget) => start.elapsed() >= budget,
+            None => false,
+        };
+        if confirmed || out_of_time {
+            break;
+        }
     }
 
-    ciphers
-        .into_par_iter()
-        .map(|cipher| improve(cipher, &words, ngram))
-        .reduce(
-            || improve(SimpSub::with_rand_key(), &words, ngram),
-            |accu, cur| {
-                if accu.1 > cur.1 {
-                    accu
-                } else {
-                    cur
-                }
-            },
-        )
+    CrackReport {
+        hills: hills.len(),
+        best_hits: count_best(&hills),
+        candidates: best_distinct(hills, text, top),
+    }
+}
+
+/// Number of results that have the best score
+fn count_best(results: &[(SimpSub, f64)]) -> usize {
+    let best = results
+        .iter()
+        .map(|r| r.1)
+        .fold(f64::NEG_INFINITY, f64::max);
+    results.iter().filter(|r| r.1 == best).count()
 }
 
-fn improve(cipher: SimpSub, words: &[String], ngram: &NGram) -> (SimpSub, f64) {
+/// The `top` best results with different plaintexts, best first. Ties keep the order of `results`.
+fn best_distinct(mut results: Vec<(SimpSub, f64)>, text: &str, top: usize) -> Vec<Candidate> {
+    results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+    let mut seen = HashSet::new();
+    let mut candidates = Vec::with_capacity(top);
+    for (cipher, score) in results {
+        if candidates.len() >= top.max(1) {
+            break;
+        }
+        let plaintext = cipher.decrypt(text);
+        if seen.insert(plaintext.clone()) {
+            candidates.push(Candidate {
+                cipher,
+                score,
+                plaintext,
+            });
+        }
+    }
+    candidates
+}
+
+/// Independent rngs for `n` parallel workers, derived in order from `seed`
+pub fn worker_rngs(seed: u64, n: usize) -> Vec<StdRng> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n).map(|_| StdRng::seed_from_u64(rng.gen())).collect()
+}
+
+/// Hill climb from `cipher` until no single swap improves the score, never moving letters in `locked`
+///
+/// `locked` is indexed by the position of the plain letter in the alphabet
+pub fn climb(
+    cipher: SimpSub,
+    text: &str,
+    fitness: &Fitness,
+    locked: &[bool; 26],
+) -> (SimpSub, f64) {
+    let lower = text.to_lowercase();
+    let words: Vec<String> = lower.split_whitespace().map(alpha::rm_punct).collect();
+
+    climb_words(cipher, &words, fitness, locked)
+}
+
+/// Same as `climb()`, on text already split into words
+fn climb_words(
+    cipher: SimpSub,
+    words: &[String],
+    fitness: &Fitness,
+    locked: &[bool; 26],
+) -> (SimpSub, f64) {
+    let mut result = (cipher, f64::NEG_INFINITY);
+    loop {
+        let prev_score = result.1;
+        result = improve(result.0, words, fitness, locked);
+        if result.1 <= prev_score {
+            break result;
+        }
+    }
+}
+
+/// Nothing locked; for use with `improve()`
+const NO_LOCKS: [bool; 26] = [false; 26];
+
+/// One pass of trying every swap of two letters, keeping those that improve the score
+///
+/// The pass is exhaustive and in a fixed order, so it needs no rng; results depend only on the starting key.
+fn improve(
+    cipher: SimpSub,
+    words: &[String],
+    fitness: &Fitness,
+    locked: &[bool; 26],
+) -> (SimpSub, f64) {
     let mut cipher = cipher;
-    let mut score = cipher.de_score(&words, ngram);
-    let letters = cipher.key_as_str().0.chars().collect::<Vec<char>>();
+    let mut score = cipher.de_score(words, fitness);
 
-    for (i, c1) in letters[0..letters.len()].iter().enumerate() {
-        for c2 in letters[i..letters.len()].iter() {
-            let new_cipher = cipher.swap_letters_at(*c1, *c2);
+    for p0 in 0..26 {
+        for p1 in p0 + 1..26 {
+            if locked[p0 as usize] || locked[p1 as usize] {
+                continue;
+            }
+            cipher.swap(p0, p1);
 
-            let new_score = new_cipher.de_score(&words, ngram);
+            let new_score = cipher.de_score(words, fitness);
             if new_score > score {
-                cipher = new_cipher;
                 score = new_score;
+            } else {
+                cipher.swap(p0, p1);
             }
         }
     }
     (cipher, score)
+}
 
-    // let mut rep: usize = 0;
-
-    // let mut rng = rand::thread_rng();
-
-    // loop {
-    //     let new_cipher = cipher.clone();
-    //     new_cipher.swap_rand_letters(&mut rng);
-    //     let new_score = new_cipher.de_score(&words, ngram);
-    //     if new_score > score {
-    //         cipher = new_cipher;
-    //         score = new_score;
-    //         rep = 0;
-    //     } else {
-    //         rep += 1;
-    //     }
-
-    //     if rep > rep_limit {
-    //         break (cipher, score);
-    //     }
-    // }
+/// Temperature schedule for `anneal_crack`
+#[derive(Clone, Debug)]
+pub struct AnnealSchedule {
+    /// Starting temperature, in units of log10 quadgram score per letter
+    pub temp: f64,
+    /// Temperature is multiplied by this after every iteration
+    pub cooling: f64,
+    /// Number of candidate keys tried in each restart
+    pub iterations: usize,
+    /// Number of independent restarts, run in parallel
+    pub restarts: usize,
 }
 
-/// Crack text under `limit` number of trials
-pub fn crack(text: &str, limit: usize, ngram: &NGram) -> SimpSub {
+impl Default for AnnealSchedule {
+    fn default() -> Self {
+        Self {
+            temp: 0.2,
+            cooling: 0.9997,
+            iterations: 20000,
+            restarts: 16,
+        }
+    }
+}
+
+/// Run simulated annealing from `schedule.restarts` random keys and keep the `top` best results with different
+/// plaintexts. With `known`, a key and the plain letters of it to lock as from `known_letters()`, every restart
+/// starts with those letters and never moves them.
+///
+/// Each restart has its own rng derived from `seed`, so the same seed always gives the same keys.
+pub fn anneal_crack(
+    text: &str,
+    fitness: &Fitness,
+    schedule: &AnnealSchedule,
+    known: Option<&(SimpSub, [bool; 26])>,
+    seed: u64,
+    top: usize,
+) -> CrackReport {
     let lower = text.to_lowercase();
-    let mut len3: Vec<String> = vec![];
-    let mut len2: Vec<String> = vec![];
-    let mut len1: Vec<String> = vec![];
-
-    // get all short words
-    let words: Vec<String> = lower
-        .split_whitespace()
-        .map(|word| {
-            let word = alpha::rm_punct(word);
-            match word.len() {
-                1 => len1.push(word.clone()),
-                2 => len2.push(word.clone()),
-                3 => len3.push(word.clone()),
-                _ => {}
-            }
-            word
+    let words: Vec<String> = lower.split_whitespace().map(alpha::rm_punct).collect();
+
+    let restarts: Vec<(SimpSub, f64)> = worker_rngs(seed, schedule.restarts.max(1))
+        .into_par_iter()
+        .map(|mut rng| {
+            let cipher = SimpSub::with_rand_key(&mut rng);
+            let (cipher, locked) = match known {
+                Some((placed, locked)) => (cipher.with_locked(placed, locked), locked),
+                None => (cipher, &NO_LOCKS),
+            };
+            anneal(cipher, &words, fitness, schedule, locked, &mut rng)
         })
         .collect();
+    CrackReport {
+        hills: restarts.len(),
+        best_hits: count_best(&restarts),
+        candidates: best_distinct(restarts, text, top),
+    }
+}
 
-    let mut freqs1 = freq_list(&len1);
-
-    let mut cipher = SimpSub::with_rand_key();
+/// Random walk over keys that sometimes accepts a worse key, with decreasing probability as the temperature drops;
+/// letters in `locked` never move
+fn anneal(
+    cipher: SimpSub,
+    words: &[String],
+    fitness: &Fitness,
+    schedule: &AnnealSchedule,
+    locked: &[bool; 26],
+    rng: &mut StdRng,
+) -> (SimpSub, f64) {
+    // scale temperature by text length so the same schedule works for short and long texts
+    let nletters = words.iter().map(|word| word.len()).sum::<usize>().max(1) as f64;
+    let mut temp = schedule.temp * nletters;
 
-    // characters that shouldn't be touched
-    let mut letters_used: HashSet<char, BuildCharHasher> = HashSet::with_hasher(BuildCharHasher);
+    let mut cipher = cipher;
+    let mut score = cipher.de_score(words, fitness);
+    let mut best = (cipher.clone(), score);
 
-    // the only one-letter words in english are 'a' and 'i', and 'a' is more common, so try both
-    // if len1.len() == 1, it could be a or i;
-    //      try both
-    if freqs1.len() == 1 {
-        let c = len1[0].chars().next().unwrap();
-        cipher = choose_better(
-            cipher.with_key_change(c, 'a'),
-            cipher.with_key_change(c, 'i'),
-            &words,
-            ngram,
-        )
-        .cipher;
-    } else if len1.len() == 2 {
-        //  one is a and the other is i, but 'a' has higher probability
-        cipher.change_key(freqs1.pop().unwrap().0.chars().next().unwrap(), 'a');
-        cipher.change_key(freqs1.pop().unwrap().0.chars().next().unwrap(), 'i');
-    }
-
-    let freqs2 = freq_list(&len2);
-
-    if !freqs2.is_empty() {
-        // now deal with two letter words
-        for (word2, _freq) in freqs2.into_iter().rev() {
-            for crib in alpha::CRIBS_L2.iter() {
-                letters_used.extend(crib.chars());
-                if let Ok(new_cipher) = cipher.match_crib(word2.as_str(), crib, &letters_used) {
-                    let result = choose_better(cipher, new_cipher, &words, ngram);
-                    cipher = result.cipher;
-                    let crib_not_used = result.first;
-                    if crib_not_used {
-                        crib.chars().for_each(|c| {
-                            letters_used.remove(&c);
-                        });
-                    }
-                }
+    let free: Vec<u8> = (0..26).filter(|p| !locked[*p as usize]).collect();
+    if free.len() < 2 {
+        return best;
+    }
+    for _ in 0..schedule.iterations {
+        // two different free letters, drawn like `swap_rand_letters()`
+        let i = rng.gen_range(0, free.len());
+        let j = (i + rng.gen_range(1, free.len())) % free.len();
+        let (p0, p1) = (free[i], free[j]);
+        cipher.swap(p0, p1);
+        let new_score = cipher.de_score(words, fitness);
+        let delta = new_score - score;
+
+        if delta > 0. || rng.gen::<f64>() < (delta / temp).exp() {
+            score = new_score;
+            if score > best.1 {
+                best = (cipher.clone(), score);
             }
+        } else {
+            cipher.swap(p0, p1);
         }
+        temp *= schedule.cooling;
     }
-    println!("Done pre-cracking");
-    // do the same for len3 cribs
-    let mut rng = rand::thread_rng();
-    for _ in 0..limit {
-        let new_cipher = cipher.clone();
-        new_cipher.swap_rand_letters(&mut rng);
-        cipher = choose_better(cipher, new_cipher, &words, ngram).cipher
+
+    best
+}
+
+/// Most crib placements `crib_crack()` climbs from
+const MAX_CRIB_PLACEMENTS: usize = 256;
+
+/// Crack text that contains the known plaintext `cribs`. Every placement of the cribs on the ciphertext letters
+/// where the repeated letters line up and the cribs agree with each other sets and locks the crib letters, and
+/// the rest of the key is hill climbed from `hills` random keys per placement. Returns the `top` best keys with
+/// different plaintexts, or None if no placement fits.
+///
+/// The starting keys are drawn in order from an rng seeded with `seed`, so the same seed always gives the same
+/// keys, as in `rand_crack()`.
+pub fn crib_crack(
+    text: &str,
+    cribs: &[Crib],
+    fitness: &Fitness,
+    hills: usize,
+    seed: u64,
+    top: usize,
+) -> Option<CrackReport> {
+    let lower = text.to_lowercase();
+    let letters: String = lower.chars().filter(char::is_ascii_lowercase).collect();
+
+    let mut placements = vec![];
+    place_cribs(
+        &letters,
+        cribs,
+        &SimpSub::from_mapper(IDENTITY),
+        &NO_LOCKS,
+        &mut placements,
+    );
+    if placements.is_empty() {
+        return None;
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut starts = Vec::with_capacity(placements.len() * hills.max(1));
+    for (placed, locked) in placements.iter() {
+        for _ in 0..hills.max(1) {
+            let cipher = SimpSub::with_rand_key(&mut rng).with_locked(placed, locked);
+            starts.push((cipher, locked));
+        }
     }
-    cipher
+
+    let words: Vec<String> = lower.split_whitespace().map(alpha::rm_punct).collect();
+    let hills: Vec<(SimpSub, f64)> = starts
+        .into_par_iter()
+        .map(|(cipher, locked)| climb_words(cipher, &words, fitness, locked))
+        .collect();
+    Some(CrackReport {
+        hills: hills.len(),
+        best_hits: count_best(&hills),
+        candidates: best_distinct(hills, text, top),
+    })
 }
 
-struct SubComp {
-    cipher: SimpSub,
-    first: bool,
-    score: f64,
+/// Add every consistent placement of `cribs` on `letters` to `placements`, as the key that matches them and the
+/// letters to lock, up to `MAX_CRIB_PLACEMENTS`; `cipher` and `locked` have the cribs placed so far
+fn place_cribs(
+    letters: &str,
+    cribs: &[Crib],
+    cipher: &SimpSub,
+    locked: &[bool; 26],
+    placements: &mut Vec<(SimpSub, [bool; 26])>,
+) {
+    let (crib, rest) = match cribs.split_first() {
+        Some(split) => split,
+        None => {
+            placements.push((cipher.clone(), *locked));
+            return;
+        }
+    };
+    let len = crib.text.len();
+    if len > letters.len() {
+        return;
+    }
+
+    let last = letters.len() - len;
+    let positions = match crib.position {
+        Some(pos) => pos..(pos + 1).min(last + 1),
+        None => 0..last + 1,
+    };
+    let crib_pattern = pattern::pattern(crib.text.as_bytes());
+    for pos in positions {
+        if placements.len() >= MAX_CRIB_PLACEMENTS {
+            return;
+        }
+        let window = &letters[pos..pos + len];
+        if pattern::pattern(window.as_bytes()) != crib_pattern {
+            continue;
+        }
+        if let Ok(new_cipher) = cipher.match_crib(window, &crib.text, locked) {
+            let mut new_locked = *locked;
+            for b in crib.text.bytes() {
+                new_locked[(b - b'a') as usize] = true;
+            }
+            place_cribs(letters, rest, &new_cipher, &new_locked, placements);
+        }
+    }
+}
+
+/// The letters of the best word pattern key of `text` that several words agree on, as `crack()` locks them: a key
+/// that has them, and the plain letters to lock. None if no key fits the words.
+pub fn known_letters(text: &str, patterns: &PatternDict) -> Option<(SimpSub, [bool; 26])> {
+    let key = pattern::solve(text, patterns, 1).into_iter().next()?;
+    let lower = text.to_lowercase();
+    let words: Vec<String> = lower.split_whitespace().map(alpha::rm_punct).collect();
+    Some((
+        SimpSub::from_partial(&key),
+        confirmed_letters(&key, &words, patterns),
+    ))
 }
 
-/// Returns the better cipher and true if cipher0 is used
-fn choose_better(cipher0: SimpSub, cipher1: SimpSub, words: &[String], ngram: &NGram) -> SubComp {
-    let plain0 = cipher0.decrypt_words_to_chars(words);
-    // let wc0 = dict.count_words(&plain0);
-    let plain1 = cipher1.decrypt_words_to_chars(words);
-    // let wc1 = dict.count_words(&plain1);
-    let score0 = ngram.score_chars(&plain0);
-    let score1 = ngram.score_chars(&plain1);
-
-    if score0 > score1 {
-        SubComp {
-            cipher: cipher0,
-            first: true,
-            score: score0,
+/// Different dictionary words a letter of a pattern key must be in for `crack()` to lock it. A nonsense word
+/// that happens to match a real one, like "toves" taken for "topes", only gets one letter wrong that way.
+const MIN_CONFIRMING_WORDS: usize = 2;
+
+/// Plain letters of `partial` that are in at least `MIN_CONFIRMING_WORDS` different `words` that decrypt to
+/// dictionary words, indexed like the locks of `climb()`
+fn confirmed_letters(partial: &PartialKey, words: &[String], patterns: &PatternDict) -> [bool; 26] {
+    let mut distinct: Vec<&str> = words.iter().map(String::as_str).collect();
+    distinct.sort_unstable();
+    distinct.dedup();
+
+    let mut counts = [0; 26];
+    for word in distinct {
+        let plain: Option<String> = word
+            .bytes()
+            .map(|b| match b {
+                b'a'..=b'z' => partial[(b - b'a') as usize].map(|p| (b'a' + p) as char),
+                _ => None,
+            })
+            .collect();
+        let plain = match plain {
+            Some(plain) if patterns.matches(word).contains(&plain.as_str()) => plain,
+            _ => continue,
+        };
+        let mut seen = [false; 26];
+        for b in plain.bytes() {
+            seen[(b - b'a') as usize] = true;
         }
-    } else {
-        SubComp {
-            cipher: cipher1,
-            first: false,
-            score: score1,
+        for (count, seen) in counts.iter_mut().zip(seen.iter()) {
+            *count += *seen as usize;
         }
     }
+
+    let mut locked = NO_LOCKS;
+    for (lock, count) in locked.iter_mut().zip(counts.iter()) {
+        *lock = *count >= MIN_CONFIRMING_WORDS;
+    }
+    locked
+}
+
+/// Number of word pattern keys `crack()` climbs from
+const PATTERN_KEYS: usize = 16;
+
+/// Crack a text that keeps its word boundaries: fit the cipher words to dictionary words with the same letter
+/// pattern (see `pattern::solve()`), then hill climb from every key that fits, keeping the letters that several
+/// words agree on. Returns the `top` best keys with different plaintexts, or None if no key fits.
+///
+/// Needs no rng; the result only depends on the text and the dictionary.
+pub fn crack(
+    text: &str,
+    patterns: &PatternDict,
+    fitness: &Fitness,
+    top: usize,
+) -> Option<CrackReport> {
+    let keys = pattern::solve(text, patterns, PATTERN_KEYS);
+    if keys.is_empty() {
+        return None;
+    }
+
+    let lower = text.to_lowercase();
+    let words: Vec<String> = lower.split_whitespace().map(alpha::rm_punct).collect();
+    let hills: Vec<(SimpSub, f64)> = keys
+        .par_iter()
+        .map(|key| {
+            let locked = confirmed_letters(key, &words, patterns);
+            climb_words(SimpSub::from_partial(key), &words, fitness, &locked)
+        })
+        .collect();
+    Some(CrackReport {
+        hills: hills.len(),
+        best_hits: count_best(&hills),
+        candidates: best_distinct(hills, text, top),
+    })
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::test_text::{ATBASH, PLAIN};
+    use crate::{dict::Dict, ngram::NGram};
 
     use crate::alpha;
 
@@ -419,12 +980,12 @@ mod tests {
     fn test_change_key() {
         let mut key = SimpSub::new(alpha::ASCII_LOWER_STR, alpha::ASCII_LOWER_STR).unwrap();
         key.change_key('a', 't');
-        assert_eq!(*key.mapper.get(&'a').unwrap(), 't');
-        assert_eq!(*key.mapper.get(&'t').unwrap(), 'a');
+        assert_eq!(key.encrypt("a"), "t");
+        assert_eq!(key.encrypt("t"), "a");
 
         key.change_key('t', 'b');
-        assert_eq!(*key.mapper.get(&'b').unwrap(), 'a');
-        assert_eq!(*key.mapper.get(&'t').unwrap(), 'b');
+        assert_eq!(key.encrypt("b"), "a");
+        assert_eq!(key.encrypt("t"), "b");
     }
 
     #[test]
@@ -435,8 +996,7 @@ mod tests {
         )
         .unwrap();
         assert_eq!(
-            "REGRT EAF YOFR ASHIAZTMOEAS LWZLMOMWMOGFL DART MG A MTVM ZB AFASBNOFU STMMTK YKTJWTFEOTL."
-                .to_ascii_lowercase(),
+            "rEgrt eaf yofr ashiaztmoeas lwzlmomwmogfl dart mg a mtvm zb afasbnofu stmmtk yktjwtfeotl.",
             encoder
                 .encrypt("dCode can find alphabetical substitutions made to a text by analyzing letter frequencies."));
     }
@@ -450,8 +1010,7 @@ mod tests {
         .unwrap();
 
         assert_eq!(
-            "dcode can find alphabetical substitutions made to a text by analyzing letter frequencies."
-                .to_ascii_lowercase(),
+            "DCODE CAN FIND ALPHABETICAL SUBSTITUTIONS MADE TO A TEXT BY ANALYZING LETTER FREQUENCIES.",
             encoder
                 .encrypt("REGRT EAF YOFR ASHIAZTMOEAS LWZLMOMWMOGFL DART MG A MTVM ZB AFASBNOFU STMMTK YKTJWTFEOTL.")
         );
@@ -464,14 +1023,20 @@ mod tests {
 
         assert_eq!(
             "dcode can find alphabetical substitutions made to a text by analyzing letter frequencies.",
-            encoder.decrypt("REGRT EAF YOFR ASHIAZTMOEAS LWZLMOMWMOGFL DART MG A MTVM ZB AFASBNOFU STMMTK YKTJWTFEOTL.")
+            encoder.decrypt("regrt eaf yofr ashiaztmoeas lwzlmomwmogfl dart mg a mtvm zb afasbnofu stmmtk yktjwtfeotl.")
+        );
+
+        assert_eq!(
+            "DCode can find...\n  Alphabetical!",
+            encoder.decrypt("REgrt eaf yofr...\n  Ashiaztmoeas!")
         );
     }
 
     #[test]
     fn test_keys_different_after_swap() {
         let cipher0 = SimpSub::new(alpha::ASCII_LOWER_STR, alpha::ASCII_LOWER_STR).unwrap();
-        let cipher1 = cipher0.swap_rand_letters(&mut rand::thread_rng());
+        let mut cipher1 = cipher0.clone();
+        cipher1.swap_rand_letters(&mut rand::thread_rng());
 
         let (f0, k0) = cipher0.key_as_str();
         let (f1, k1) = cipher1.key_as_str();
@@ -482,9 +1047,10 @@ mod tests {
     #[test]
     fn test_keys_diff_many_swaps() {
         let mut rng = rand::thread_rng();
-        let mut cipher = SimpSub::with_rand_key();
+        let mut cipher = SimpSub::with_rand_key(&mut rng);
         for i in 0..10000 {
-            let new_cipher = cipher.swap_rand_letters(&mut rng);
+            let mut new_cipher = cipher.clone();
+            new_cipher.swap_rand_letters(&mut rng);
             assert_ne!(
                 cipher.key_as_str().1,
                 new_cipher.key_as_str().1,
@@ -494,4 +1060,286 @@ mod tests {
             cipher = new_cipher;
         }
     }
+
+    #[test]
+    fn test_swap_undo() {
+        let mut rng = rand::thread_rng();
+        let mut cipher = SimpSub::with_rand_key(&mut rng);
+        let (_, orig) = cipher.key_as_str();
+
+        let (p0, p1) = cipher.swap_rand_letters(&mut rng);
+        assert_eq!(
+            cipher.decrypt(&cipher.encrypt(alpha::ASCII_LOWER_STR)),
+            alpha::ASCII_LOWER_STR
+        );
+        cipher.swap(p0, p1);
+        assert_eq!(cipher.key_as_str().1, orig);
+    }
+
+    #[test]
+    fn test_seeded_runs_repeat() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let text = "gsv hrnkov hfyhgrgfgrlm xrksvi rh jfrgv vzhb gl yivzp";
+        let schedule = AnnealSchedule {
+            iterations: 2000,
+            ..AnnealSchedule::default()
+        };
+        let run = || {
+            (
+                rand_crack(text, &fitness, &StopRule::hills(20), 42, 1).candidates[0]
+                    .cipher
+                    .key_as_str(),
+                anneal_crack(text, &fitness, &schedule, None, 42, 1).candidates[0]
+                    .cipher
+                    .key_as_str(),
+            )
+        };
+
+        let single_thread = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(run);
+        assert_eq!(run(), single_thread);
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_anneal_crack() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let text = ATBASH;
+
+        let report = anneal_crack(text, &fitness, &AnnealSchedule::default(), None, 1, 1);
+        assert!(report.candidates[0]
+            .plaintext
+            .starts_with("the simple substitution cipher is quite easy to break. even though"));
+        assert_eq!(report.hills, AnnealSchedule::default().restarts);
+        assert!(report.best_hits >= 1);
+
+        // a key with no structure to it
+        let key = SimpSub::new(alpha::ASCII_LOWER_STR, "qwertyuiopasdfghjklzxcvbnm").unwrap();
+        let text = key.encrypt(PLAIN);
+        let report = anneal_crack(&text, &fitness, &AnnealSchedule::default(), None, 1, 1);
+        assert_eq!(report.candidates[0].plaintext, PLAIN);
+    }
+
+    #[test]
+    fn test_anneal_crack_known_letters() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let words = crate::data::read_words(None).unwrap();
+        let dict = Dict::new(words.lines().collect());
+        let patterns = PatternDict::new(&dict);
+        // quadgrams alone prefer "twas prillig" and "peware the fapperwock"
+        let text = include_str!("../samples/sample5.txt");
+
+        let known = known_letters(text, &patterns);
+        assert!(known.is_some());
+        let report = anneal_crack(
+            text,
+            &fitness,
+            &AnnealSchedule::default(),
+            known.as_ref(),
+            1,
+            1,
+        );
+        let plaintext = &report.candidates[0].plaintext;
+        assert!(plaintext.starts_with("twas brillig, and the slithy toves"));
+        assert!(plaintext.contains("beware the jabberwock, my son!"));
+    }
+
+    #[test]
+    fn test_top_candidates_distinct() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let text = "gsv hrnkov hfyhgrgfgrlm xrksvi rh jfrgv vzhb gl yivzp";
+
+        let candidates = rand_crack(text, &fitness, &StopRule::hills(30), 7, 5).candidates;
+        assert!(!candidates.is_empty() && candidates.len() <= 5);
+        assert!(candidates.windows(2).all(|w| w[0].score >= w[1].score));
+        let plaintexts: HashSet<&String> = candidates.iter().map(|c| &c.plaintext).collect();
+        assert_eq!(candidates.len(), plaintexts.len());
+        assert_eq!(candidates[0].plaintext, candidates[0].cipher.decrypt(text));
+    }
+
+    #[test]
+    fn test_match_crib() {
+        let key = SimpSub::from_mapper(IDENTITY);
+        let matched = key.match_crib("xyzzx", "hello", &NO_LOCKS);
+        assert!(matched.is_err());
+        let matched = key.match_crib("xyzzw", "hello", &NO_LOCKS).unwrap();
+        assert_eq!(matched.decrypt("xyzzw"), "hello");
+        // x can't decrypt to both h and o, nor can x and w both decrypt to o
+        assert!(key.match_crib("xyzzx", "hellh", &NO_LOCKS).is_ok());
+        assert!(key.match_crib("xyzzw", "hellh", &NO_LOCKS).is_err());
+
+        let mut locked = NO_LOCKS;
+        locked[(b'h' - b'a') as usize] = true;
+        assert!(matched.match_crib("q", "h", &locked).is_err());
+        assert!(matched.match_crib("x", "h", &locked).is_ok());
+    }
+
+    #[test]
+    fn test_parse_crib() {
+        assert_eq!(
+            "Attack at dawn!".parse(),
+            Ok(Crib {
+                text: "attackatdawn".to_owned(),
+                position: None
+            })
+        );
+        assert_eq!(
+            "at dawn@ 12".parse(),
+            Ok(Crib {
+                text: "atdawn".to_owned(),
+                position: Some(12)
+            })
+        );
+        assert!("dawn@first".parse::<Crib>().is_err());
+        assert!("42".parse::<Crib>().is_err());
+    }
+
+    #[test]
+    fn test_crib_crack() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let text = ATBASH;
+        let cribs = ["substitution".parse().unwrap()];
+
+        let report = crib_crack(text, &cribs, &fitness, 4, 1, 1).unwrap();
+        assert!(report.candidates[0]
+            .plaintext
+            .starts_with("the simple substitution cipher is quite easy to break"));
+        assert_eq!(report.hills % 4, 0);
+        let again = crib_crack(text, &cribs, &fitness, 4, 1, 1).unwrap();
+        assert_eq!(
+            again.candidates[0].cipher.key_as_str(),
+            report.candidates[0].cipher.key_as_str()
+        );
+
+        // the crib doesn't fit at that position
+        let cribs = ["substitution@0".parse().unwrap()];
+        assert!(crib_crack(text, &cribs, &fitness, 4, 1, 1).is_none());
+
+        // a key with no structure to it, and the crib where it starts
+        let key = SimpSub::new(alpha::ASCII_LOWER_STR, "qwertyuiopasdfghjklzxcvbnm").unwrap();
+        let text = key.encrypt(PLAIN);
+        let cribs = ["several alphabets@247".parse().unwrap()];
+        let report = crib_crack(&text, &cribs, &fitness, 4, 1, 1).unwrap();
+        assert_eq!(report.candidates[0].plaintext, PLAIN);
+    }
+
+    #[test]
+    fn test_pattern_crack() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let words = crate::data::read_words(None).unwrap();
+        let dict = Dict::new(words.lines().collect());
+        let patterns = PatternDict::new(&dict);
+        let text = ATBASH;
+
+        let report = crack(text, &patterns, &fitness, 1).unwrap();
+        assert!(report.candidates[0]
+            .plaintext
+            .starts_with("the simple substitution cipher is quite easy to break. even though"));
+        assert!(crack("", &patterns, &fitness, 1).is_none());
+    }
+
+    #[test]
+    fn test_stop_on_confirmations() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        let text = ATBASH;
+        let stop = StopRule {
+            max_hills: 10 * HILL_BATCH,
+            confirmations: Some(3),
+            budget: None,
+        };
+
+        let report = rand_crack(text, &fitness, &stop, 3, 1);
+        assert!(report.best_hits >= 3);
+        assert!(report.hills < stop.max_hills);
+        assert!(report.hills.is_multiple_of(HILL_BATCH));
+
+        let fixed = rand_crack(text, &fitness, &StopRule::hills(5), 3, 1);
+        assert_eq!(5, fixed.hills);
+        assert!(fixed.best_hits >= 1);
+    }
+
+    #[test]
+    fn test_new_rejects_bad_keys() {
+        assert!(SimpSub::new("ab", "cc").is_err());
+        assert!(SimpSub::new("a", "b").is_err());
+        assert!(SimpSub::new("a1", "bc").is_err());
+        assert!(SimpSub::new("ab", "ba").is_ok());
+    }
+
+    #[test]
+    fn test_constructors() {
+        let to = |cipher: SimpSub| cipher.key_as_str().1;
+        assert_eq!(to(SimpSub::atbash()), "zyxwvutsrqponmlkjihgfedcba");
+        assert_eq!(to(SimpSub::caesar(3)), "defghijklmnopqrstuvwxyzabc");
+        assert_eq!(
+            to(SimpSub::affine(5, 8).unwrap()),
+            "insxchmrwbglqvafkpuzejotyd"
+        );
+        assert!(SimpSub::affine(13, 0).is_err());
+        assert_eq!(
+            to(SimpSub::keyword("zebras", 0, false)),
+            "zebrascdfghijklmnopqtuvwxy"
+        );
+        assert_eq!(
+            to(SimpSub::keyword("Zebras!", 2, false)),
+            "xyzebrascdfghijklmnopqtuvw"
+        );
+        assert_eq!(
+            to(SimpSub::keyword("zebras", 0, true)),
+            "yxwvutqponmlkjihgfdcsarbez"
+        );
+        assert_eq!(to(SimpSub::keyword("", 0, true)), to(SimpSub::atbash()));
+        assert_eq!(to(SimpSub::keyword("", 23, false)), to(SimpSub::caesar(3)));
+    }
+
+    #[test]
+    fn test_family() {
+        assert_eq!(SimpSub::atbash().family(), Some(KeyFamily::Atbash));
+        assert_eq!(SimpSub::caesar(-1).family(), Some(KeyFamily::Caesar(25)));
+        assert_eq!(
+            SimpSub::affine(5, 8).unwrap().family(),
+            Some(KeyFamily::Affine { a: 5, b: 8 })
+        );
+
+        let keyword = |keyword: &str, offset: u8, reversed: bool| {
+            Some(KeyFamily::Keyword {
+                keyword: keyword.to_string(),
+                offset,
+                reversed,
+            })
+        };
+        for &(offset, reversed) in [(0, false), (7, false), (0, true), (20, true)].iter() {
+            let cipher = SimpSub::keyword("kryptos", offset, reversed);
+            assert_eq!(cipher.family(), keyword("kryptos", offset as u8, reversed));
+        }
+        // only distinct letters, and "c" continues the rest of the alphabet
+        assert_eq!(
+            SimpSub::keyword("attack", 0, false).family(),
+            keyword("atck", 0, false)
+        );
+        assert_eq!(
+            SimpSub::keyword("zebrac", 0, false).family(),
+            keyword("zebr", 0, false)
+        );
+        assert_eq!(
+            SimpSub::keyword("zebrac", 0, false)
+                .family()
+                .unwrap()
+                .to_string(),
+            "keyword zebr"
+        );
+
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(SimpSub::with_rand_key(&mut rng).family(), None);
+    }
 }
@@ -0,0 +1,13 @@
+//! Texts shared by the tests of the ciphers and crackers
+
+/// English text long enough for every cracker to work on
+pub const PLAIN: &str = "the simple substitution cipher is quite easy to break. even though the number of keys is \
+    a really big number, there is a lot of redundancy and other statistical properties of english text that make \
+    it quite easy to determine a reasonably good key. polyalphabetic ciphers spread those properties over several \
+    alphabets, which hides them from a plain frequency count and makes the analyst's job harder.";
+
+/// `PLAIN` in Atbash
+pub const ATBASH: &str = "gsv hrnkov hfyhgrgfgrlm xrksvi rh jfrgv vzhb gl yivzp. vevm gslfts gsv mfnyvi lu pvbh rh \
+    z ivzoob yrt mfnyvi, gsviv rh z olg lu ivwfmwzmxb zmw lgsvi hgzgrhgrxzo kilkvigrvh lu vmtorhs gvcg gszg nzpv \
+    rg jfrgv vzhb gl wvgvinrmv z ivzhlmzyob tllw pvb. klobzokszyvgrx xrksvih hkivzw gslhv kilkvigrvh levi hvevizo \
+    zokszyvgh, dsrxs srwvh gsvn uiln z kozrm uivjfvmxb xlfmg zmw nzpvh gsv zmzobhg'h qly sziwvi.";
@@ -0,0 +1,291 @@
+#![allow(dead_code)]
+
+use std::fmt;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::{alpha, fitness::Fitness, simp_sub};
+
+/// Longest key the cracker will consider
+const MAX_KEY_LEN: usize = 12;
+
+/// Keys up to this length are cracked by trying every column order; longer ones by hill climbing
+const MAX_EXHAUSTIVE_LEN: usize = 8;
+
+/// Number of random restarts when hill climbing column orders
+const CLIMB_RESTARTS: usize = 200;
+
+/// Columnar transposition: letters are written into rows under the key, then read off column by column
+/// in the alphabetical order of the key letters
+pub struct Columnar {
+    /// `order[i]` is the column read `i`th
+    order: Vec<usize>,
+    /// Letter used to fill up the last row when encrypting; None leaves the last row irregular
+    pad: Option<char>,
+}
+
+impl fmt::Debug for Columnar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Columnar---")?;
+        writeln!(
+            f,
+            "column order: {}",
+            self.order
+                .iter()
+                .map(|c| (c + 1).to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        )?;
+        Ok(())
+    }
+}
+
+impl Columnar {
+    /// Key columns are read in the alphabetical order of `keyword`; repeated letters are read left to right.
+    /// If `pad` is true, the last row is filled up with 'x' when encrypting.
+    pub fn new(keyword: &str, pad: bool) -> Result<Self, &'static str> {
+        let letters: Option<Vec<u8>> = keyword
+            .chars()
+            .map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .collect();
+        let letters = match letters {
+            Some(letters) if !letters.is_empty() => letters,
+            _ => return Err(
+                "Error creating a key for Columnar: keyword must be a non-empty string of letters",
+            ),
+        };
+
+        let mut order: Vec<usize> = (0..letters.len()).collect();
+        order.sort_by_key(|i| letters[*i]);
+
+        Ok(Self {
+            order,
+            pad: if pad { Some('x') } else { None },
+        })
+    }
+
+    /// Column read order; `order()[i]` is the column read `i`th
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    fn from_order(order: Vec<usize>) -> Self {
+        Self { order, pad: None }
+    }
+
+    /// Encrypt the letters of text, keeping their case; everything else is dropped
+    pub fn encrypt(&self, text: &str) -> String {
+        let mut letters: Vec<char> = text.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+        if let Some(pad) = self.pad {
+            while !letters.len().is_multiple_of(self.order.len()) {
+                letters.push(pad);
+            }
+        }
+
+        let ncols = self.order.len();
+        self.order
+            .iter()
+            .flat_map(|col| letters.iter().skip(*col).step_by(ncols))
+            .collect()
+    }
+
+    /// Decrypt the letters of text, keeping their case; everything else is dropped
+    pub fn decrypt(&self, text: &str) -> String {
+        let letters: Vec<char> = text.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+        self.unscramble(&letters).into_iter().collect()
+    }
+
+    /// Undo the transposition on any sequence of items
+    fn unscramble<T: Copy>(&self, items: &[T]) -> Vec<T> {
+        let ncols = self.order.len();
+        let nrows = items.len().div_ceil(ncols);
+        // columns left of this one have one letter more than the rest
+        let full_cols = match items.len() % ncols {
+            0 => ncols,
+            n => n,
+        };
+
+        let mut cols: Vec<&[T]> = vec![&[]; ncols];
+        let mut rest = items;
+        for col in self.order.iter() {
+            let len = if *col < full_cols { nrows } else { nrows - 1 };
+            let (head, tail) = rest.split_at(len);
+            cols[*col] = head;
+            rest = tail;
+        }
+
+        (0..items.len())
+            .map(|i| cols[i % ncols][i / ncols])
+            .collect()
+    }
+
+    /// Crack text by trying every key length up to `MAX_KEY_LEN` and searching column orders for the one
+    /// whose decryption scores best. Only the n-gram part of `fitness` applies, since word boundaries are lost.
+    /// Returns None if there are not enough letters, or if even the best decryption is not plausible English,
+    /// as when the text is not a transposition.
+    ///
+    /// Long keys are hill climbed from random orders drawn from rngs derived from `seed`, so the same seed always
+    /// gives the same key.
+    pub fn crack(text: &str, fitness: &Fitness, seed: u64) -> Option<(Self, f64)> {
+        let letters: Vec<u8> = text
+            .chars()
+            .filter_map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .collect();
+        if letters.len() < 4 {
+            return None;
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let max_len = MAX_KEY_LEN.min(letters.len() / 2);
+        (2..=max_len)
+            .map(|len| {
+                if len <= MAX_EXHAUSTIVE_LEN {
+                    crack_exhaustive(&letters, len, fitness)
+                } else {
+                    crack_climb(&letters, len, fitness, rng.gen())
+                }
+            })
+            .fold(None, |best: Option<(Self, f64)>, cur| match best {
+                // prefer the shorter key on ties
+                Some(best) if best.1 >= cur.1 => Some(best),
+                _ => Some(cur),
+            })
+            .filter(|(cipher, _)| fitness.is_plausible(&cipher.decrypt(text)))
+    }
+
+    fn score(&self, letters: &[u8], fitness: &Fitness) -> f64 {
+        fitness.score_letters(&self.unscramble(letters))
+    }
+}
+
+/// Try every column order of length `len`
+fn crack_exhaustive(letters: &[u8], len: usize, fitness: &Fitness) -> (Columnar, f64) {
+    let mut order: Vec<usize> = (0..len).collect();
+    let mut best = (order.clone(), f64::NEG_INFINITY);
+    loop {
+        let score = Columnar::from_order(order.clone()).score(letters, fitness);
+        if score > best.1 {
+            best = (order.clone(), score);
+        }
+        if !next_permutation(&mut order) {
+            break;
+        }
+    }
+    (Columnar::from_order(best.0), best.1)
+}
+
+/// Hill climb over column orders of length `len` from random starts, swapping two columns at a time. Each start
+/// has its own rng derived from `seed`, and ties go to the earlier start.
+fn crack_climb(letters: &[u8], len: usize, fitness: &Fitness, seed: u64) -> (Columnar, f64) {
+    simp_sub::worker_rngs(seed, CLIMB_RESTARTS)
+        .into_par_iter()
+        .map(|mut rng| {
+            let mut order: Vec<usize> = (0..len).collect();
+            order.shuffle(&mut rng);
+            let mut cipher = Columnar::from_order(order);
+            let mut score = cipher.score(letters, fitness);
+
+            let mut improved = true;
+            while improved {
+                improved = false;
+                for i in 0..len {
+                    for j in i + 1..len {
+                        cipher.order.swap(i, j);
+                        let new_score = cipher.score(letters, fitness);
+                        if new_score > score {
+                            score = new_score;
+                            improved = true;
+                        } else {
+                            cipher.order.swap(i, j);
+                        }
+                    }
+                }
+            }
+            (cipher, score)
+        })
+        .reduce_with(|accu, cur| if accu.1 >= cur.1 { accu } else { cur })
+        .unwrap()
+}
+
+/// Rearrange into the next permutation in lexicographic order; returns false after the last one
+fn next_permutation(items: &mut [usize]) -> bool {
+    let pivot = match (1..items.len()).rev().find(|i| items[i - 1] < items[*i]) {
+        Some(i) => i - 1,
+        None => return false,
+    };
+    let successor = (pivot + 1..items.len())
+        .rev()
+        .find(|i| items[*i] > items[pivot])
+        .unwrap();
+    items.swap(pivot, successor);
+    items[pivot + 1..].reverse();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ngram::NGram;
+    use crate::test_text::{ATBASH, PLAIN};
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let cipher = Columnar::new("zebras", false).unwrap();
+        assert_eq!(
+            "EVLNACDTESEAROFODEECWIREE",
+            cipher.encrypt("WE ARE DISCOVERED. FLEE AT ONCE")
+        );
+        assert_eq!(
+            "WEAREDISCOVEREDFLEEATONCE",
+            cipher.decrypt("EVLNACDTESEAROFODEECWIREE")
+        );
+
+        let cipher = Columnar::new("zebras", true).unwrap();
+        let encrypted = cipher.encrypt("WE ARE DISCOVERED. FLEE AT ONCE");
+        assert_eq!(30, encrypted.len());
+        assert_eq!("WEAREDISCOVEREDFLEEATONCExxxxx", cipher.decrypt(&encrypted));
+    }
+
+    #[test]
+    fn test_next_permutation() {
+        let mut items = [0, 1, 2];
+        let mut count = 1;
+        while next_permutation(&mut items) {
+            count += 1;
+        }
+        assert_eq!(6, count);
+        assert_eq!([2, 1, 0], items);
+    }
+
+    #[test]
+    fn test_crack() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        // a rotated order, which decrypts to the plaintext starting with its last few letters, can score a
+        // little better depending on how the last row falls; PLAIN alone comes out rotated under "cipher"
+        let plain = format!("{} for most messages.", PLAIN);
+
+        for keyword in &["cipher", "german", "strawberry"] {
+            let cipher = Columnar::new(keyword, false).unwrap();
+            let (cracked, _score) = Columnar::crack(&cipher.encrypt(&plain), &fitness, 1).unwrap();
+            assert_eq!(cipher.order, cracked.order);
+        }
+
+        // atbash, which no column order decrypts
+        assert!(Columnar::crack(ATBASH, &fitness, 1).is_none());
+    }
+
+    #[test]
+    fn test_seeded_climb_repeats() {
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+        // too short to decrypt, so the climbs end on different keys
+        let letters: Vec<u8> = "xjiucnpyypmirhxgzuypxgwxbozuhphmwnzirhmpkcyz"
+            .bytes()
+            .map(|b| b - b'a')
+            .collect();
+        let run = |seed| crack_climb(&letters, 10, &fitness, seed).0.order;
+        assert_eq!(run(3), run(3));
+    }
+}
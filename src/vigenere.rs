@@ -0,0 +1,267 @@
+#![allow(dead_code)]
+
+use std::{collections::HashMap, fmt};
+
+use crate::{alpha, caesar, fitness::Fitness};
+
+/// Longest key the cracker will consider
+const MAX_KEY_LEN: usize = 20;
+
+/// Number of key lengths that are fully solved and compared by ngram score
+const NUM_CANDIDATES: usize = 4;
+
+/// Fewest letters per column for the column statistics to mean anything
+const MIN_COLUMN_LEN: usize = 4;
+
+pub struct Vigenere {
+    /// Shift of each key letter; 'a' is 0
+    shifts: Vec<u8>,
+}
+
+impl fmt::Debug for Vigenere {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Vigenere---")?;
+        writeln!(f, "key: {}", self.key())?;
+        Ok(())
+    }
+}
+
+impl Vigenere {
+    pub fn new(key: &str) -> Result<Self, &'static str> {
+        let shifts: Option<Vec<u8>> = key
+            .chars()
+            .map(|c| alpha::get_letter_pos(c.to_ascii_lowercase()))
+            .collect();
+
+        match shifts {
+            Some(shifts) if !shifts.is_empty() => Ok(Self { shifts }),
+            _ => {
+                Err("Error creating a key for Vigenere: key must be a non-empty string of letters")
+            }
+        }
+    }
+
+    pub fn key(&self) -> String {
+        self.shifts.iter().map(|s| (b'a' + s) as char).collect()
+    }
+
+    /// The shift of every key letter, if they are all the same, making this a Caesar cipher
+    pub fn shift(&self) -> Option<u8> {
+        let first = self.shifts[0];
+        if self.shifts.iter().all(|shift| *shift == first) {
+            Some(first)
+        } else {
+            None
+        }
+    }
+
+    /// Encrypt text with key; letters keep their case, and other chars are left unchanged and don't use up a key letter
+    pub fn encrypt(&self, text: &str) -> String {
+        self.transform(text, |pos, shift| (pos + shift) % 26)
+    }
+
+    pub fn decrypt(&self, text: &str) -> String {
+        self.transform(text, |pos, shift| (pos + 26 - shift) % 26)
+    }
+
+    fn transform(&self, text: &str, shift_fn: impl Fn(u8, u8) -> u8) -> String {
+        let mut key_pos = 0;
+        text.chars()
+            .map(|c| match alpha::get_letter_pos(c.to_ascii_lowercase()) {
+                Some(pos) => {
+                    let shift = self.shifts[key_pos % self.shifts.len()];
+                    key_pos += 1;
+                    alpha::match_case((b'a' + shift_fn(pos, shift)) as char, c)
+                }
+                None => c,
+            })
+            .collect()
+    }
+
+    /// Crack text by guessing likely key lengths, solving every column as a Caesar cipher,
+    /// and keeping the key whose decryption scores best
+    ///
+    /// Assumes text is in lowercase. Returns None if there are too few letters to work with, or if even the best
+    /// decryption is not plausible English, as when the text is not a Vigenere cipher.
+    pub fn crack(text: &str, fitness: &Fitness) -> Option<(Self, f64)> {
+        let letters = only_letters(text);
+        if letters.len() < 2 * MIN_COLUMN_LEN {
+            return None;
+        }
+
+        key_len_candidates(&letters)
+            .into_iter()
+            .map(|len| {
+                let shifts = (0..len)
+                    .map(|offset| solve_column(&column(&letters, len, offset)))
+                    .collect();
+                let cipher = Self { shifts };
+                let score = fitness.score_text(&cipher.decrypt(text));
+                (cipher, score)
+            })
+            .fold(None, |best: Option<(Self, f64)>, cur| match best {
+                // prefer the shorter key on ties, since a repeated key decrypts the same way
+                Some(best)
+                    if best.1 > cur.1
+                        || (best.1 == cur.1 && best.0.shifts.len() <= cur.0.shifts.len()) =>
+                {
+                    Some(best)
+                }
+                _ => Some(cur),
+            })
+            .filter(|(cipher, _)| fitness.is_plausible(&cipher.decrypt(text)))
+    }
+}
+
+/// Letter positions of every lower ascii letter in text
+fn only_letters(text: &str) -> Vec<u8> {
+    text.chars().filter_map(alpha::get_letter_pos).collect()
+}
+
+/// Every `len`th letter, starting from `offset`
+fn column(letters: &[u8], len: usize, offset: usize) -> Vec<u8> {
+    letters.iter().skip(offset).step_by(len).copied().collect()
+}
+
+fn to_string(letters: &[u8]) -> String {
+    letters.iter().map(|l| (b'a' + l) as char).collect()
+}
+
+/// Find the Caesar shift of a column whose letter frequencies are closest to English
+fn solve_column(column: &[u8]) -> u8 {
+    (0..26u8)
+        .map(|shift| {
+            let shifted: Vec<u8> = column.iter().map(|l| (l + 26 - shift) % 26).collect();
+            let percent = caesar::get_percentage(&to_string(&shifted));
+            (shift, caesar::chi_squared(&percent))
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .unwrap()
+        .0
+}
+
+/// Average index of coincidence of the columns when letters are split by key length `len`
+pub fn periodic_ioc(letters: &[u8], len: usize) -> f64 {
+    (0..len)
+        .map(|offset| caesar::index_of_coincidence(&to_string(&column(letters, len, offset))))
+        .sum::<f64>()
+        / len as f64
+}
+
+/// Friedman's estimate of the key length from the index of coincidence of the whole text
+pub fn friedman_estimate(letters: &[u8]) -> f64 {
+    let n = letters.len() as f64;
+    let ioc = caesar::index_of_coincidence(&to_string(letters));
+    let denom = (n - 1.) * ioc - alpha::RANDOM_IOC * n + alpha::ENGLISH_IOC;
+    if denom <= 0. {
+        return n;
+    }
+    (alpha::ENGLISH_IOC - alpha::RANDOM_IOC) * n / denom
+}
+
+/// Count how many distances between repeated trigrams are divisible by each key length
+///
+/// Returned list is indexed by key length; entries 0 and 1 are always 0
+pub fn kasiski(letters: &[u8]) -> Vec<usize> {
+    let mut last_seen: HashMap<&[u8], usize> = HashMap::new();
+    let mut votes = vec![0; MAX_KEY_LEN + 1];
+
+    for (i, trigram) in letters.windows(3).enumerate() {
+        if let Some(prev) = last_seen.insert(trigram, i) {
+            let dist = i - prev;
+            for (len, vote) in votes.iter_mut().enumerate().skip(2) {
+                if dist % len == 0 {
+                    *vote += 1;
+                }
+            }
+        }
+    }
+
+    votes
+}
+
+/// Likely key lengths, best first, combining periodic IoC, Kasiski examination and Friedman's test
+fn key_len_candidates(letters: &[u8]) -> Vec<usize> {
+    let max_len = MAX_KEY_LEN.min(letters.len() / MIN_COLUMN_LEN).max(1);
+
+    // multiples of the real key length have about the same IoC, so favour the shorter ones
+    let iocs: Vec<(usize, f64)> = (1..=max_len)
+        .map(|len| (len, periodic_ioc(letters, len)))
+        .collect();
+    let best_ioc = iocs.iter().map(|e| e.1).fold(0., f64::max);
+    let mut candidates: Vec<usize> = iocs
+        .iter()
+        .filter(|e| e.1 >= 0.9 * best_ioc)
+        .map(|e| e.0)
+        .take(NUM_CANDIDATES / 2)
+        .collect();
+
+    // divisors of the real key length get at least as many votes, so favour the longer ones
+    let votes = kasiski(letters);
+    let max_votes = votes.iter().copied().max().unwrap_or(0);
+    if max_votes > 0 {
+        let kasiski_len = (2..=max_len)
+            .rev()
+            .find(|len| votes[*len] * 10 >= max_votes * 8)
+            .unwrap_or(1);
+        candidates.push(kasiski_len);
+    }
+
+    let friedman = friedman_estimate(letters).round() as usize;
+    candidates.push(friedman.max(1).min(max_len));
+
+    let mut ranked: Vec<(usize, f64)> = iocs;
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    candidates.extend(ranked.into_iter().map(|e| e.0));
+
+    let mut seen = vec![false; max_len + 1];
+    candidates.retain(|len| !std::mem::replace(&mut seen[*len], true));
+    candidates.truncate(NUM_CANDIDATES);
+
+    // short texts have noisy statistics, so always give a plain Caesar shift a chance
+    if !candidates.contains(&1) {
+        candidates.push(1);
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ngram::NGram;
+    use crate::test_text::{ATBASH, PLAIN};
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let cipher = Vigenere::new("LEMON").unwrap();
+        assert_eq!("lxfopv efrnhr", cipher.encrypt("attack atdawn"));
+        assert_eq!("attack atdawn", cipher.decrypt("lxfopv efrnhr"));
+        assert_eq!("Attack, at dawn!", cipher.decrypt("Lxfopv, ef rnhr!"));
+    }
+
+    #[test]
+    fn test_shift() {
+        assert_eq!(Vigenere::new("b").unwrap().shift(), Some(1));
+        assert_eq!(Vigenere::new("ddd").unwrap().shift(), Some(3));
+        assert_eq!(Vigenere::new("lemon").unwrap().shift(), None);
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(Vigenere::new("").is_err());
+        assert!(Vigenere::new("le mon").is_err());
+    }
+
+    #[test]
+    fn test_crack() {
+        let cipher = Vigenere::new("cipher").unwrap();
+        let quads = NGram::load_default_quad().unwrap();
+        let fitness = Fitness::new(&quads);
+
+        let (cracked, _score) = Vigenere::crack(&cipher.encrypt(PLAIN), &fitness).unwrap();
+        assert_eq!("cipher", cracked.key());
+
+        // atbash, which no vigenere key decrypts
+        assert!(Vigenere::crack(ATBASH, &fitness).is_none());
+    }
+}